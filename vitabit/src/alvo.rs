@@ -0,0 +1,355 @@
+// src/alvo.rs
+
+use std::cmp::Ordering;
+
+/// Alvo mais fácil permitido (≈ 4 zeros hexadecimais no início do hash); é também o do gênesis
+pub const BITS_LIMITE: u32 = 0x1f00ffff;
+
+/// Quanto o alvo pode mudar, no máximo, em um reajuste (para cima ou para baixo)
+pub const FATOR_MAXIMO_AJUSTE: i64 = 4;
+
+/// Alvo de prova de trabalho: o hash do cabeçalho, lido como número big-endian
+/// de 256 bits, precisa ser menor ou igual a ele
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Alvo(U256);
+
+impl Alvo {
+    /// Alvo mais fácil permitido
+    pub fn limite() -> Alvo {
+        Alvo::de_bits(BITS_LIMITE).expect("BITS_LIMITE é um alvo válido")
+    }
+
+    /// Lê a forma compacta: expoente no byte alto (tamanho em bytes) e mantissa de
+    /// 23 bits. Recusa alvo negativo, zero ou que não cabe em 256 bits.
+    pub fn de_bits(bits: u32) -> Option<Alvo> {
+        let expoente = bits >> 24;
+        let mantissa = (bits & 0x007f_ffff) as u64;
+        if bits & 0x0080_0000 != 0 || mantissa == 0 {
+            return None;
+        }
+
+        let valor = if expoente <= 3 {
+            U256::de_u64(mantissa >> (8 * (3 - expoente)))
+        } else {
+            let deslocamento = 8 * (expoente - 3);
+            if deslocamento + (64 - mantissa.leading_zeros()) > 256 {
+                return None;
+            }
+            U256::de_u64(mantissa).shl(deslocamento)
+        };
+
+        (valor != U256::ZERO).then_some(Alvo(valor))
+    }
+
+    /// Forma compacta do alvo (arredondada para baixo na mantissa)
+    pub fn para_bits(&self) -> u32 {
+        let mut tamanho = self.0.bits().div_ceil(8);
+        let mut mantissa = if tamanho <= 3 {
+            self.0.baixo() << (8 * (3 - tamanho))
+        } else {
+            self.0.shr(8 * (tamanho - 3)).baixo()
+        };
+        // O bit 0x00800000 é o sinal: se a mantissa o ocupa, ganha mais um byte
+        if mantissa & 0x0080_0000 != 0 {
+            mantissa >>= 8;
+            tamanho += 1;
+        }
+        mantissa as u32 | (tamanho << 24)
+    }
+
+    /// Indica se um hash (32 bytes, big-endian) atinge o alvo
+    pub fn aceita(&self, hash: &[u8; 32]) -> bool {
+        U256::de_be_bytes(hash) <= self.0
+    }
+
+    /// Indica se um hash em hexadecimal atinge o alvo
+    pub fn aceita_hex(&self, hash: &str) -> bool {
+        match hex::decode(hash).ok().and_then(|b| <[u8; 32]>::try_from(b).ok()) {
+            Some(bytes) => self.aceita(&bytes),
+            None => false,
+        }
+    }
+
+    /// Alvo em hexadecimal big-endian (64 dígitos), comparável ao hash do cabeçalho
+    pub fn para_hex(&self) -> String {
+        hex::encode(self.0.para_be_bytes())
+    }
+
+    /// Número esperado de hashes para atingir o alvo: 2^256 / (alvo + 1)
+    pub fn trabalho(&self) -> u128 {
+        if self.0 == U256::MAX {
+            return 1;
+        }
+        // 2^256 / (alvo + 1) = (~alvo / (alvo + 1)) + 1, sem sair dos 256 bits
+        let trabalho = self.0.not().div(&self.0.mais_um()).mais_um();
+        trabalho.para_u128().unwrap_or(u128::MAX)
+    }
+
+    /// Novo alvo depois de um período que levou `tempo_real` segundos em vez de
+    /// `tempo_esperado`: proporcional ao tempo, limitado a FATOR_MAXIMO_AJUSTE
+    /// vezes para cada lado e nunca mais fácil que o limite
+    pub fn ajustar(&self, tempo_real: i64, tempo_esperado: i64) -> Alvo {
+        let esperado = tempo_esperado.max(1);
+        let real = tempo_real.clamp(esperado / FATOR_MAXIMO_AJUSTE, esperado * FATOR_MAXIMO_AJUSTE);
+
+        // Multiplica primeiro; se estourar os 256 bits, divide antes (o alvo é então
+        // grande o bastante para a perda nos bits baixos não importar)
+        let (real, esperado) = (real.max(1) as u64, esperado as u64);
+        let novo = match self.0.mul_u64(real) {
+            Some(v) => v.div_u64(esperado),
+            None => self.0.div_u64(esperado).mul_u64(real).unwrap_or(U256::MAX),
+        };
+
+        let novo = Alvo(novo).min(Alvo::limite());
+        // Passa pela forma compacta, que é o que vai no cabeçalho
+        Alvo::de_bits(novo.para_bits()).unwrap_or(novo)
+    }
+}
+
+/// Inteiro sem sinal de 256 bits, em palavras de 64 bits da menos para a mais significativa
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct U256([u64; 4]);
+
+impl U256 {
+    const ZERO: U256 = U256([0; 4]);
+    const MAX: U256 = U256([u64::MAX; 4]);
+
+    fn de_u64(valor: u64) -> U256 {
+        U256([valor, 0, 0, 0])
+    }
+
+    fn de_be_bytes(bytes: &[u8; 32]) -> U256 {
+        let mut palavras = [0u64; 4];
+        for (i, pedaco) in bytes.chunks(8).enumerate() {
+            palavras[3 - i] = u64::from_be_bytes(pedaco.try_into().unwrap());
+        }
+        U256(palavras)
+    }
+
+    fn para_be_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, pedaco) in bytes.chunks_mut(8).enumerate() {
+            pedaco.copy_from_slice(&self.0[3 - i].to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Os 64 bits menos significativos
+    fn baixo(&self) -> u64 {
+        self.0[0]
+    }
+
+    fn para_u128(self) -> Option<u128> {
+        if self.0[2] != 0 || self.0[3] != 0 {
+            return None;
+        }
+        Some(((self.0[1] as u128) << 64) | self.0[0] as u128)
+    }
+
+    /// Quantidade de bits significativos
+    fn bits(&self) -> u32 {
+        for i in (0..4).rev() {
+            if self.0[i] != 0 {
+                return 64 * i as u32 + 64 - self.0[i].leading_zeros();
+            }
+        }
+        0
+    }
+
+    fn bit(&self, n: u32) -> bool {
+        self.0[(n / 64) as usize] >> (n % 64) & 1 == 1
+    }
+
+    fn shl(&self, n: u32) -> U256 {
+        let mut r = [0u64; 4];
+        let (palavras, bits) = ((n / 64) as usize, n % 64);
+        for i in (palavras..4).rev() {
+            r[i] = self.0[i - palavras] << bits;
+            if bits > 0 && i > palavras {
+                r[i] |= self.0[i - palavras - 1] >> (64 - bits);
+            }
+        }
+        U256(r)
+    }
+
+    fn shr(&self, n: u32) -> U256 {
+        let mut r = [0u64; 4];
+        let (palavras, bits) = ((n / 64) as usize, n % 64);
+        for (i, palavra) in r.iter_mut().enumerate().take(4 - palavras.min(4)) {
+            *palavra = self.0[i + palavras] >> bits;
+            if bits > 0 && i + palavras + 1 < 4 {
+                *palavra |= self.0[i + palavras + 1] << (64 - bits);
+            }
+        }
+        U256(r)
+    }
+
+    fn not(&self) -> U256 {
+        U256(self.0.map(|p| !p))
+    }
+
+    fn mais_um(&self) -> U256 {
+        let mut r = self.0;
+        for palavra in r.iter_mut() {
+            let (v, estourou) = palavra.overflowing_add(1);
+            *palavra = v;
+            if !estourou {
+                break;
+            }
+        }
+        U256(r)
+    }
+
+    fn menos(&self, outro: &U256) -> U256 {
+        let mut r = [0u64; 4];
+        let mut emprestimo = false;
+        for (i, palavra) in r.iter_mut().enumerate() {
+            let (v, e1) = self.0[i].overflowing_sub(outro.0[i]);
+            let (v, e2) = v.overflowing_sub(emprestimo as u64);
+            *palavra = v;
+            emprestimo = e1 || e2;
+        }
+        U256(r)
+    }
+
+    fn mul_u64(&self, m: u64) -> Option<U256> {
+        let mut r = [0u64; 4];
+        let mut vai_um: u128 = 0;
+        for (i, palavra) in r.iter_mut().enumerate() {
+            let v = self.0[i] as u128 * m as u128 + vai_um;
+            *palavra = v as u64;
+            vai_um = v >> 64;
+        }
+        (vai_um == 0).then_some(U256(r))
+    }
+
+    fn div_u64(&self, d: u64) -> U256 {
+        let mut r = [0u64; 4];
+        let mut resto: u128 = 0;
+        for i in (0..4).rev() {
+            let atual = (resto << 64) | self.0[i] as u128;
+            r[i] = (atual / d as u128) as u64;
+            resto = atual % d as u128;
+        }
+        U256(r)
+    }
+
+    /// Divisão longa bit a bit; divisor zero dá o máximo
+    fn div(&self, divisor: &U256) -> U256 {
+        if *divisor == U256::ZERO {
+            return U256::MAX;
+        }
+        let mut quociente = U256::ZERO;
+        let mut resto = U256::ZERO;
+        for n in (0..self.bits()).rev() {
+            resto = resto.shl(1);
+            if self.bit(n) {
+                resto.0[0] |= 1;
+            }
+            if resto >= *divisor {
+                resto = resto.menos(divisor);
+                quociente.0[(n / 64) as usize] |= 1 << (n % 64);
+            }
+        }
+        quociente
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, outro: &Self) -> Ordering {
+        self.0.iter().rev().cmp(outro.0.iter().rev())
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, outro: &Self) -> Option<Ordering> {
+        Some(self.cmp(outro))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Alvo do gênesis do Bitcoin, referência conhecida da forma compacta
+    const BITS_BITCOIN: u32 = 0x1d00ffff;
+
+    fn alvo(bits: u32) -> Alvo {
+        Alvo::de_bits(bits).unwrap()
+    }
+
+    #[test]
+    fn le_a_forma_compacta() {
+        assert_eq!(alvo(BITS_BITCOIN).para_hex(), format!("00000000ffff{}", "0".repeat(52)));
+        assert_eq!(alvo(0x03123456).para_hex(), format!("{}123456", "0".repeat(58)));
+        // Com expoente menor que 3 a mantissa perde os bytes baixos
+        assert_eq!(alvo(0x01123456).para_hex(), format!("{}12", "0".repeat(62)));
+        assert_eq!(Alvo::limite(), alvo(BITS_LIMITE));
+    }
+
+    #[test]
+    fn recusa_alvo_negativo_zero_ou_grande_demais() {
+        assert_eq!(Alvo::de_bits(0x1d800000 | 0xffff), None); // bit de sinal
+        assert_eq!(Alvo::de_bits(0x1d000000), None);
+        assert_eq!(Alvo::de_bits(0x01003456), None); // a mantissa some no deslocamento
+        assert!(Alvo::de_bits(0x2100ffff).is_some()); // 0xffff << 240 ainda cabe em 256 bits
+        assert_eq!(Alvo::de_bits(0x2200ffff), None);
+        assert_eq!(Alvo::de_bits(0xff123456), None);
+    }
+
+    #[test]
+    fn forma_compacta_vai_e_volta() {
+        for bits in [BITS_BITCOIN, BITS_LIMITE, 0x1b0404cb, 0x03123456, 0x02008000, 0x2100ffff] {
+            assert_eq!(alvo(bits).para_bits(), bits, "{:08x}", bits);
+        }
+    }
+
+    #[test]
+    fn normaliza_a_mantissa() {
+        // Mantissa com zeros à esquerda encolhe o expoente
+        assert_eq!(alvo(0x1e0000ff).para_bits(), 0x1d00ff00);
+        assert_eq!(alvo(0x1d00ff00), alvo(0x1e0000ff));
+        // Mantissa que ocuparia o bit de sinal ganha mais um byte
+        assert_eq!(Alvo(U256::de_u64(0x80)).para_bits(), 0x02008000);
+        assert_eq!(Alvo(U256::de_u64(0x800000)).para_bits(), 0x04008000);
+        // Bytes perdidos com expoente pequeno não voltam
+        assert_eq!(alvo(0x01123456).para_bits(), 0x01120000);
+    }
+
+    #[test]
+    fn trabalho_do_alvo() {
+        assert_eq!(alvo(BITS_BITCOIN).trabalho(), 4_295_032_833);
+        assert_eq!(Alvo(U256::MAX).trabalho(), 1);
+    }
+
+    #[test]
+    fn ajuste_proporcional_ao_tempo() {
+        let esperado = 1200;
+        assert_eq!(alvo(BITS_BITCOIN).ajustar(esperado, esperado), alvo(BITS_BITCOIN));
+        assert_eq!(alvo(BITS_BITCOIN).ajustar(2 * esperado, esperado), alvo(0x1d01fffe));
+    }
+
+    #[test]
+    fn ajuste_limitado_a_quatro_vezes() {
+        let esperado = 1200;
+        let inicial = alvo(BITS_BITCOIN);
+
+        // Lento demais: no máximo 4x mais fácil
+        assert_eq!(inicial.ajustar(4 * esperado, esperado), alvo(0x1d03fffc));
+        assert_eq!(inicial.ajustar(100 * esperado, esperado), alvo(0x1d03fffc));
+
+        // Rápido demais: no máximo 4x mais difícil, mesmo com tempo zero ou negativo
+        assert_eq!(inicial.ajustar(esperado / 4, esperado), alvo(0x1c3fffc0));
+        assert_eq!(inicial.ajustar(1, esperado), alvo(0x1c3fffc0));
+        assert_eq!(inicial.ajustar(-esperado, esperado), alvo(0x1c3fffc0));
+    }
+
+    #[test]
+    fn ajuste_nunca_passa_do_limite() {
+        let esperado = 1200;
+        assert_eq!(Alvo::limite().ajustar(4 * esperado, esperado), Alvo::limite());
+        let metade_do_limite = alvo(0x1f007fff);
+        assert_eq!(metade_do_limite.ajustar(4 * esperado, esperado), Alvo::limite());
+        assert_eq!(metade_do_limite.ajustar(esperado, esperado), metade_do_limite);
+    }
+}
@@ -0,0 +1,508 @@
+// src/armazem.rs
+
+use crate::block::Block;
+use crate::utxo::DesfazerBloco;
+use crate::codificacao::{codificar, decodificar};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Arquivo com os blocos serializados, um após o outro
+pub const ARQUIVO_DADOS: &str = "blocos.dat";
+
+/// Arquivo de índice: posição de cada bloco no arquivo de dados e o topo da cadeia
+pub const ARQUIVO_INDICE: &str = "blocos.idx";
+
+/// Registros para desfazer blocos conectados (outputs que cada bloco gastou)
+pub const ARQUIVO_DESFAZER: &str = "desfazer.dat";
+
+/// Bytes que abrem cada registro, em todos os arquivos
+const MAGIC_REGISTRO: [u8; 4] = *b"VBLK";
+
+/// magic (4) + tamanho (4) + checksum (4)
+const TAMANHO_CABECALHO: u64 = 4 + 4 + 4;
+
+/// Registros maiores que isso só podem ser lixo de uma gravação interrompida
+const MAX_REGISTRO: u32 = 64 * 1024 * 1024;
+
+/// Resumo de um bloco guardado: o bastante para montar a árvore de blocos
+/// e calcular a dificuldade sem ler o bloco inteiro do disco
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntradaIndice {
+    pub hash: String,
+    pub previous_hash: String,
+    pub index: u64,
+    pub timestamp: i64,
+    pub bits: u32,
+    pub extra_reward: u64,
+    pub posicao: u64, // início do registro no arquivo de dados
+    pub tamanho: u32, // bytes do bloco serializado
+}
+
+impl EntradaIndice {
+    /// Primeiro byte depois do registro no arquivo de dados
+    fn fim(&self) -> u64 {
+        self.posicao + TAMANHO_CABECALHO + self.tamanho as u64
+    }
+}
+
+/// Eventos gravados no arquivo de índice
+#[derive(Debug, Serialize, Deserialize)]
+enum RegistroIndice {
+    Bloco(EntradaIndice),
+    Topo(String),     // a cadeia ativa passou a terminar nesse hash
+    Invalido(String), // bloco descartado por violar as regras de consenso
+    Desfazer { hash: String, posicao: u64, tamanho: u32 }, // registro de desfazer do bloco
+}
+
+/// Onde os registros ficam guardados
+#[derive(Debug)]
+enum Meio {
+    Disco {
+        dados: File,
+        desfazer: File,
+        indice: File,
+        caminho_dados: PathBuf,
+        caminho_desfazer: PathBuf,
+    },
+    Memoria { dados: Vec<u8>, desfazer: Vec<u8> },
+}
+
+/// Arquivo de onde um trecho é lido
+#[derive(Debug, Clone, Copy)]
+enum Arquivo {
+    Dados,
+    Desfazer,
+}
+
+/// Armazém de blocos só de acréscimo. Cada bloco é gravado uma única vez no
+/// arquivo de dados, com checksum, e localizado pelo índice mantido em memória.
+/// Os blocos em si só são lidos quando alguém pede.
+#[derive(Debug)]
+pub struct ArmazemBlocos {
+    meio: Meio,
+    tamanho_dados: u64,
+    tamanho_desfazer: u64,
+    entradas: HashMap<String, EntradaIndice>, // hash → posição no arquivo de dados
+    desfazer: HashMap<String, (u64, u32)>,    // hash → (posição, tamanho) no arquivo de desfazer
+    ordem: Vec<String>,                       // ordem de gravação: pais antes dos filhos
+    topo: Option<String>,
+    invalidos: HashSet<String>,
+}
+
+impl ArmazemBlocos {
+    /// Armazém sem arquivos, para cadeias montadas só em memória
+    pub fn em_memoria() -> Self {
+        ArmazemBlocos {
+            meio: Meio::Memoria { dados: Vec::new(), desfazer: Vec::new() },
+            tamanho_dados: 0,
+            tamanho_desfazer: 0,
+            entradas: HashMap::new(),
+            desfazer: HashMap::new(),
+            ordem: Vec::new(),
+            topo: None,
+            invalidos: HashSet::new(),
+        }
+    }
+
+    /// Abre (ou cria) o armazém em `diretorio`. Registros incompletos deixados
+    /// por uma queda no meio de uma gravação são descartados, e blocos gravados
+    /// sem a entrada correspondente no índice são reindexados.
+    pub fn abrir(diretorio: &Path) -> io::Result<Self> {
+        fs::create_dir_all(diretorio)?;
+        let caminho_dados = diretorio.join(ARQUIVO_DADOS);
+        let abrir = |caminho: &Path| OpenOptions::new().read(true).append(true).create(true).open(caminho);
+        let mut indice = abrir(&diretorio.join(ARQUIVO_INDICE))?;
+        let dados = abrir(&caminho_dados)?;
+        let tamanho_dados = dados.metadata()?.len();
+        let caminho_desfazer = diretorio.join(ARQUIVO_DESFAZER);
+        let desfazer = abrir(&caminho_desfazer)?;
+        let tamanho_desfazer = desfazer.metadata()?.len();
+
+        let mut armazem = ArmazemBlocos {
+            meio: Meio::Memoria { dados: Vec::new(), desfazer: Vec::new() },
+            tamanho_dados,
+            tamanho_desfazer,
+            entradas: HashMap::new(),
+            desfazer: HashMap::new(),
+            ordem: Vec::new(),
+            topo: None,
+            invalidos: HashSet::new(),
+        };
+
+        // Índice: aplica os eventos até o primeiro registro corrompido
+        let mut bytes = Vec::new();
+        indice.read_to_end(&mut bytes)?;
+        let (registros, _) = ler_registros(&bytes);
+        let mut consumidos = 0;
+        for (posicao, payload) in registros {
+            match decodificar::<RegistroIndice>(payload) {
+                Ok(registro) => armazem.aplicar(registro),
+                Err(_) => break,
+            }
+            consumidos = posicao as usize + TAMANHO_CABECALHO as usize + payload.len();
+        }
+        if consumidos < bytes.len() {
+            println!("⚠️ Índice de blocos truncado em {} de {} bytes", consumidos, bytes.len());
+            indice.set_len(consumidos as u64)?;
+            indice.sync_all()?;
+        }
+
+        // Entradas que apontam além do fim dos dados não têm bloco para ler
+        let perdidas: Vec<String> = armazem
+            .entradas
+            .values()
+            .filter(|e| e.fim() > tamanho_dados)
+            .map(|e| e.hash.clone())
+            .collect();
+        for hash in &perdidas {
+            armazem.entradas.remove(hash);
+        }
+        armazem.ordem.retain(|h| armazem.entradas.contains_key(h));
+        if armazem.topo.as_ref().is_some_and(|t| !armazem.entradas.contains_key(t)) {
+            armazem.topo = None;
+        }
+        let antes = armazem.desfazer.len();
+        let entradas = &armazem.entradas;
+        armazem.desfazer.retain(|h, (posicao, tamanho)| {
+            entradas.contains_key(h) && *posicao + TAMANHO_CABECALHO + *tamanho as u64 <= tamanho_desfazer
+        });
+        let desfazer_perdidos = antes - armazem.desfazer.len();
+
+        armazem.meio = Meio::Disco { dados, desfazer, indice, caminho_dados, caminho_desfazer };
+        if !perdidas.is_empty() || desfazer_perdidos > 0 {
+            // Sem isso as entradas velhas passariam a apontar para registros novos
+            println!(
+                "⚠️ {} entrada(s) do índice sem dados correspondentes",
+                perdidas.len() + desfazer_perdidos
+            );
+            armazem.reescrever_indice()?;
+        }
+        armazem.recuperar_cauda()?;
+        armazem.cortar_desfazer()?;
+        Ok(armazem)
+    }
+
+    /// Registros de desfazer não indexados não têm como ser achados: o arquivo
+    /// é cortado no fim do último registro indexado
+    fn cortar_desfazer(&mut self) -> io::Result<()> {
+        let fim_indexado = self
+            .desfazer
+            .values()
+            .map(|(posicao, tamanho)| posicao + TAMANHO_CABECALHO + *tamanho as u64)
+            .max()
+            .unwrap_or(0);
+        if fim_indexado < self.tamanho_desfazer {
+            println!(
+                "⚠️ Descartando {} byte(s) não indexados no fim de {}",
+                self.tamanho_desfazer - fim_indexado,
+                ARQUIVO_DESFAZER
+            );
+            if let Meio::Disco { desfazer, .. } = &mut self.meio {
+                desfazer.set_len(fim_indexado)?;
+                desfazer.sync_all()?;
+            }
+            self.tamanho_desfazer = fim_indexado;
+        }
+        Ok(())
+    }
+
+    /// Regrava o índice inteiro a partir do estado em memória
+    fn reescrever_indice(&mut self) -> io::Result<()> {
+        let mut eventos: Vec<RegistroIndice> = self
+            .ordem
+            .iter()
+            .map(|h| RegistroIndice::Bloco(self.entradas[h].clone()))
+            .collect();
+        eventos.extend(self.invalidos.iter().cloned().map(RegistroIndice::Invalido));
+        eventos.extend(self.desfazer.iter().map(|(hash, (posicao, tamanho))| RegistroIndice::Desfazer {
+            hash: hash.clone(),
+            posicao: *posicao,
+            tamanho: *tamanho,
+        }));
+        eventos.extend(self.topo.clone().map(RegistroIndice::Topo));
+
+        if let Meio::Disco { indice, .. } = &mut self.meio {
+            indice.set_len(0)?;
+            for evento in &eventos {
+                let payload = codificar(evento);
+                indice.write_all(&montar_registro(&payload))?;
+            }
+            indice.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Reindexa blocos gravados depois da última entrada do índice e corta
+    /// o registro incompleto que possa ter ficado no fim do arquivo de dados
+    fn recuperar_cauda(&mut self) -> io::Result<()> {
+        let fim_indexado = self.entradas.values().map(|e| e.fim()).max().unwrap_or(0);
+        if fim_indexado >= self.tamanho_dados {
+            return Ok(());
+        }
+
+        let cauda = self.ler_trecho(Arquivo::Dados, fim_indexado, (self.tamanho_dados - fim_indexado) as usize)?;
+        let (registros, _) = ler_registros(&cauda);
+        let mut recuperados = Vec::new();
+        let mut fim_valido = 0;
+        for (posicao, payload) in registros {
+            match decodificar::<Block>(payload) {
+                Ok(bloco) => recuperados.push((fim_indexado + posicao, payload.len() as u32, bloco)),
+                Err(_) => break,
+            }
+            fim_valido = posicao + TAMANHO_CABECALHO + payload.len() as u64;
+        }
+
+        let novo_tamanho = fim_indexado + fim_valido;
+        if novo_tamanho < self.tamanho_dados {
+            println!("⚠️ Descartando {} byte(s) incompletos no fim de {}", self.tamanho_dados - novo_tamanho, ARQUIVO_DADOS);
+            if let Meio::Disco { dados, .. } = &mut self.meio {
+                dados.set_len(novo_tamanho)?;
+                dados.sync_all()?;
+            }
+            self.tamanho_dados = novo_tamanho;
+        }
+
+        for (posicao, tamanho, bloco) in recuperados {
+            if !self.entradas.contains_key(&bloco.hash) {
+                println!("🩹 Bloco #{} reindexado", bloco.index);
+                self.registrar(Self::entrada_de(&bloco, posicao, tamanho))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn entrada_de(bloco: &Block, posicao: u64, tamanho: u32) -> EntradaIndice {
+        EntradaIndice {
+            hash: bloco.hash.clone(),
+            previous_hash: bloco.header.previous_hash.clone(),
+            index: bloco.index,
+            timestamp: bloco.header.timestamp,
+            bits: bloco.header.bits,
+            extra_reward: bloco.header.extra_reward,
+            posicao,
+            tamanho,
+        }
+    }
+
+    /// Atualiza o estado em memória com um evento do índice
+    fn aplicar(&mut self, registro: RegistroIndice) {
+        match registro {
+            RegistroIndice::Bloco(entrada) => {
+                if !self.entradas.contains_key(&entrada.hash) {
+                    self.ordem.push(entrada.hash.clone());
+                }
+                self.entradas.insert(entrada.hash.clone(), entrada);
+            }
+            RegistroIndice::Topo(hash) => self.topo = Some(hash),
+            RegistroIndice::Invalido(hash) => {
+                self.invalidos.insert(hash);
+            }
+            RegistroIndice::Desfazer { hash, posicao, tamanho } => {
+                self.desfazer.insert(hash, (posicao, tamanho));
+            }
+        }
+    }
+
+    /// Grava um evento no índice (com fsync) e o aplica
+    fn registrar_evento(&mut self, registro: RegistroIndice) -> io::Result<()> {
+        if let Meio::Disco { indice, .. } = &mut self.meio {
+            let payload = codificar(&registro);
+            indice.write_all(&montar_registro(&payload))?;
+            indice.sync_data()?;
+        }
+        self.aplicar(registro);
+        Ok(())
+    }
+
+    fn registrar(&mut self, entrada: EntradaIndice) -> io::Result<()> {
+        self.registrar_evento(RegistroIndice::Bloco(entrada))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entradas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entradas.is_empty()
+    }
+
+    pub fn contem(&self, hash: &str) -> bool {
+        self.entradas.contains_key(hash)
+    }
+
+    pub fn entrada(&self, hash: &str) -> Option<&EntradaIndice> {
+        self.entradas.get(hash)
+    }
+
+    /// Entradas na ordem de gravação, sem os blocos marcados como inválidos
+    pub fn entradas(&self) -> impl Iterator<Item = &EntradaIndice> {
+        self.ordem
+            .iter()
+            .filter(|h| !self.invalidos.contains(*h))
+            .filter_map(|h| self.entradas.get(h))
+    }
+
+    /// Último topo da cadeia ativa registrado
+    pub fn topo(&self) -> Option<&str> {
+        self.topo.as_deref()
+    }
+
+    /// Acrescenta o bloco ao arquivo de dados e ao índice. O bloco só é
+    /// indexado depois que os dados estão no disco.
+    pub fn gravar(&mut self, bloco: &Block) -> io::Result<()> {
+        if self.contem(&bloco.hash) {
+            return Ok(());
+        }
+
+        let payload = codificar(bloco);
+        let registro = montar_registro(&payload);
+        let posicao = self.tamanho_dados;
+        match &mut self.meio {
+            Meio::Disco { dados, .. } => {
+                dados.write_all(&registro)?;
+                dados.sync_data()?;
+            }
+            Meio::Memoria { dados, .. } => dados.extend_from_slice(&registro),
+        }
+        self.tamanho_dados += registro.len() as u64;
+
+        self.registrar(Self::entrada_de(bloco, posicao, payload.len() as u32))
+    }
+
+    /// Registra o novo topo da cadeia ativa
+    pub fn marcar_topo(&mut self, hash: &str) -> io::Result<()> {
+        if self.topo.as_deref() == Some(hash) {
+            return Ok(());
+        }
+        self.registrar_evento(RegistroIndice::Topo(hash.to_string()))
+    }
+
+    /// Registra que o bloco é inválido, para não voltar à árvore ao reabrir
+    pub fn marcar_invalido(&mut self, hash: &str) -> io::Result<()> {
+        self.registrar_evento(RegistroIndice::Invalido(hash.to_string()))
+    }
+
+    /// Lê um bloco do arquivo de dados, conferindo o checksum
+    pub fn ler(&self, hash: &str) -> io::Result<Block> {
+        let entrada = self
+            .entradas
+            .get(hash)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("bloco {} não está no armazém", hash)))?;
+
+        self.ler_registro(Arquivo::Dados, hash, entrada.posicao, entrada.tamanho)
+    }
+
+    /// Guarda o que é preciso para desconectar o bloco. O registro só é
+    /// indexado depois que está no disco.
+    pub fn gravar_desfazer(&mut self, hash: &str, desfazer: &DesfazerBloco) -> io::Result<()> {
+        if self.desfazer.contains_key(hash) {
+            return Ok(());
+        }
+
+        let payload = codificar(desfazer);
+        let registro = montar_registro(&payload);
+        let posicao = self.tamanho_desfazer;
+        match &mut self.meio {
+            Meio::Disco { desfazer, .. } => {
+                desfazer.write_all(&registro)?;
+                desfazer.sync_data()?;
+            }
+            Meio::Memoria { desfazer, .. } => desfazer.extend_from_slice(&registro),
+        }
+        self.tamanho_desfazer += registro.len() as u64;
+
+        self.registrar_evento(RegistroIndice::Desfazer {
+            hash: hash.to_string(),
+            posicao,
+            tamanho: payload.len() as u32,
+        })
+    }
+
+    /// Lê o registro de desfazer de um bloco conectado
+    pub fn ler_desfazer(&self, hash: &str) -> io::Result<DesfazerBloco> {
+        let (posicao, tamanho) = *self
+            .desfazer
+            .get(hash)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("bloco {} sem registro de desfazer", hash)))?;
+        self.ler_registro(Arquivo::Desfazer, hash, posicao, tamanho)
+    }
+
+    /// Lê e desserializa um registro, conferindo o checksum
+    fn ler_registro<T: serde::de::DeserializeOwned>(&self, arquivo: Arquivo, hash: &str, posicao: u64, tamanho: u32) -> io::Result<T> {
+        let bytes = self.ler_trecho(arquivo, posicao, (TAMANHO_CABECALHO + tamanho as u64) as usize)?;
+        let (registros, _) = ler_registros(&bytes);
+        let payload = registros
+            .first()
+            .map(|(_, p)| *p)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("registro do bloco {} corrompido", hash)))?;
+        decodificar(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn ler_trecho(&self, arquivo: Arquivo, posicao: u64, tamanho: usize) -> io::Result<Vec<u8>> {
+        match (&self.meio, arquivo) {
+            (Meio::Disco { caminho_dados: caminho, .. }, Arquivo::Dados)
+            | (Meio::Disco { caminho_desfazer: caminho, .. }, Arquivo::Desfazer) => {
+                let mut arquivo = File::open(caminho)?;
+                arquivo.seek(SeekFrom::Start(posicao))?;
+                let mut bytes = vec![0u8; tamanho];
+                arquivo.read_exact(&mut bytes)?;
+                Ok(bytes)
+            }
+            (Meio::Memoria { dados: bytes, .. }, Arquivo::Dados)
+            | (Meio::Memoria { desfazer: bytes, .. }, Arquivo::Desfazer) => {
+                let inicio = posicao as usize;
+                bytes
+                    .get(inicio..inicio + tamanho)
+                    .map(|b| b.to_vec())
+                    .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+            }
+        }
+    }
+}
+
+/// Primeiros 4 bytes do SHA256 duplo do payload
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(Sha256::digest(payload));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// magic | tamanho (u32 LE) | checksum | payload
+pub(crate) fn montar_registro(payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(TAMANHO_CABECALHO as usize + payload.len());
+    bytes.extend_from_slice(&MAGIC_REGISTRO);
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&checksum(payload));
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Percorre os registros em sequência, parando no primeiro incompleto ou
+/// corrompido. Devolve (posição, payload) de cada um e quantos bytes são válidos.
+pub(crate) fn ler_registros(bytes: &[u8]) -> (Vec<(u64, &[u8])>, usize) {
+    let cabecalho = TAMANHO_CABECALHO as usize;
+    let mut registros = Vec::new();
+    let mut pos = 0;
+
+    while bytes.len() - pos >= cabecalho {
+        if bytes[pos..pos + 4] != MAGIC_REGISTRO {
+            break;
+        }
+        let tamanho = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap());
+        if tamanho > MAX_REGISTRO || bytes.len() - pos - cabecalho < tamanho as usize {
+            break;
+        }
+        let payload = &bytes[pos + cabecalho..pos + cabecalho + tamanho as usize];
+        if checksum(payload) != bytes[pos + 8..pos + 12] {
+            break;
+        }
+        registros.push((pos as u64, payload));
+        pos += cabecalho + tamanho as usize;
+    }
+
+    (registros, pos)
+}
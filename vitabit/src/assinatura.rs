@@ -0,0 +1,203 @@
+// src/assinatura.rs
+
+use secp256k1::{Secp256k1, SecretKey, PublicKey, Message};
+use secp256k1::ecdsa::Signature;
+
+/// Quem assina o resumo (sighash) de uma entrada
+pub trait Signer {
+    /// Assinatura ECDSA do resumo em DER, sempre com S baixo
+    fn assinar(&self, resumo: &[u8; 32]) -> Vec<u8>;
+
+    /// Chave pública correspondente, comprimida (33 bytes)
+    fn chave_publica(&self) -> PublicKey;
+}
+
+/// Quem confere a assinatura de um resumo
+pub trait Verifier {
+    /// Aceita só DER estrito com S baixo: a mesma assinatura não tem duas codificações válidas
+    fn verificar(&self, resumo: &[u8; 32], assinatura: &[u8]) -> bool;
+}
+
+impl Signer for SecretKey {
+    fn assinar(&self, resumo: &[u8; 32]) -> Vec<u8> {
+        let mut sig = Secp256k1::signing_only().sign_ecdsa(&Message::from_digest(*resumo), self);
+        sig.normalize_s();
+        sig.serialize_der().to_vec()
+    }
+
+    fn chave_publica(&self) -> PublicKey {
+        PublicKey::from_secret_key(&Secp256k1::signing_only(), self)
+    }
+}
+
+impl Verifier for PublicKey {
+    fn verificar(&self, resumo: &[u8; 32], assinatura: &[u8]) -> bool {
+        let sig = match Signature::from_der(assinatura) {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+
+        let mut normalizada = sig;
+        normalizada.normalize_s();
+        if normalizada != sig {
+            return false;
+        }
+
+        Secp256k1::verification_only()
+            .verify_ecdsa(&Message::from_digest(*resumo), &sig, self)
+            .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Blockchain;
+    use crate::mempool::Mempool;
+    use crate::transaction::{OutPoint, Transaction, TxOutput, SIGHASH_ALL, SIGHASH_ANYONECANPAY};
+    use crate::utxo::UTXOSet;
+    use crate::wallet::Wallet;
+
+    /// Ordem do grupo da secp256k1, em big-endian
+    const ORDEM: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+        0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+    ];
+
+    fn chave() -> SecretKey {
+        SecretKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    /// A mesma assinatura com S trocado por n - S (o par "alto", também válido para a curva)
+    fn com_s_alto(der: &[u8]) -> Vec<u8> {
+        let compacta = Signature::from_der(der).unwrap().serialize_compact();
+        let mut s = [0u8; 32];
+        let mut emprestimo = 0i16;
+        for i in (0..32).rev() {
+            let d = ORDEM[i] as i16 - compacta[32 + i] as i16 - emprestimo;
+            emprestimo = (d < 0) as i16;
+            s[i] = d.rem_euclid(256) as u8;
+        }
+        let mut alta = compacta;
+        alta[32..].copy_from_slice(&s);
+        Signature::from_compact(&alta).unwrap().serialize_der().to_vec()
+    }
+
+    /// Carteira com um output de 50 e a transação que ela monta gastando-o
+    fn transacao_da_carteira() -> (Transaction, UTXOSet) {
+        let carteira = Wallet::new();
+        let mut utxos = UTXOSet::new();
+        utxos.inserir(
+            OutPoint::new(&"ab".repeat(32), 0),
+            TxOutput { value: 50, address: carteira.address.clone(), timestamp: 0 },
+        );
+        let tx = carteira.create_transaction("destino", 10, 1, &utxos, &Mempool::new()).unwrap();
+        (tx, utxos)
+    }
+
+    #[test]
+    fn assina_e_verifica() {
+        let resumo = [1u8; 32];
+        let assinatura = chave().assinar(&resumo);
+        assert!(chave().chave_publica().verificar(&resumo, &assinatura));
+        assert!(!chave().chave_publica().verificar(&[2u8; 32], &assinatura));
+
+        let outra = SecretKey::from_slice(&[8; 32]).unwrap();
+        assert!(!outra.chave_publica().verificar(&resumo, &assinatura));
+    }
+
+    #[test]
+    fn recusa_s_alto() {
+        let resumo = [1u8; 32];
+        let assinatura = chave().assinar(&resumo);
+        let alta = com_s_alto(&assinatura);
+        assert_ne!(alta, assinatura);
+
+        // A curva aceita as duas; o VitaBit só a de S baixo
+        let mut normalizada = Signature::from_der(&alta).unwrap();
+        normalizada.normalize_s();
+        assert_eq!(normalizada.serialize_der().to_vec(), assinatura);
+        assert!(!chave().chave_publica().verificar(&resumo, &alta));
+    }
+
+    #[test]
+    fn recusa_assinatura_fora_do_der() {
+        let resumo = [1u8; 32];
+        let pubkey = chave().chave_publica();
+        let assinatura = chave().assinar(&resumo);
+
+        let compacta = Signature::from_der(&assinatura).unwrap().serialize_compact();
+        assert!(!pubkey.verificar(&resumo, &compacta));
+
+        let mut com_sobra = assinatura.clone();
+        com_sobra.push(0);
+        assert!(!pubkey.verificar(&resumo, &com_sobra));
+
+        // R com um zero à esquerda desnecessário: mesmo número, codificação não mínima
+        let tamanho_r = assinatura[3] as usize;
+        let mut nao_minima = vec![0x30, assinatura[1] + 1, 0x02, assinatura[3] + 1, 0x00];
+        nao_minima.extend_from_slice(&assinatura[4..]);
+        assert_eq!(nao_minima.len(), assinatura.len() + 1);
+        assert_eq!(&nao_minima[5..5 + tamanho_r], &assinatura[4..4 + tamanho_r]);
+        assert!(!pubkey.verificar(&resumo, &nao_minima));
+
+        assert!(!pubkey.verificar(&resumo, &[]));
+    }
+
+    #[test]
+    fn transacao_da_carteira_passa_na_verificacao() {
+        let (tx, utxos) = transacao_da_carteira();
+        assert!(Blockchain::new().verify_transaction(&tx, &utxos));
+    }
+
+    #[test]
+    fn transacao_da_carteira_alterada_nao_passa() {
+        let (tx, utxos) = transacao_da_carteira();
+        let blockchain = Blockchain::new();
+
+        // Outro valor: a assinatura (SIGHASH_ALL) não cobre mais os outputs
+        let mut outputs = tx.outputs.clone();
+        outputs[0].value += 1;
+        let alterada = Transaction::new(tx.inputs.clone(), outputs);
+        assert!(!blockchain.verify_transaction(&alterada, &utxos));
+
+        // A mesma assinatura com S alto, mantendo o byte de sighash no fim
+        let mut inputs = tx.inputs.clone();
+        let mut bytes = hex::decode(&inputs[0].signature).unwrap();
+        let tipo = bytes.pop().unwrap();
+        let mut alta = com_s_alto(&bytes);
+        alta.push(tipo);
+        inputs[0].signature = hex::encode(alta);
+        let maleavel = Transaction::new(inputs, tx.outputs.clone());
+        assert!(!blockchain.verify_transaction(&maleavel, &utxos));
+    }
+
+    #[test]
+    fn sighash_all_cobre_o_valor_de_todas_as_entradas() {
+        let carteira = Wallet::new();
+        let sk = SecretKey::from_slice(&hex::decode(&carteira.private_key).unwrap()).unwrap();
+        let mut utxos = UTXOSet::new();
+        for vout in 0..2 {
+            utxos.inserir(
+                OutPoint::new(&"cd".repeat(32), vout),
+                TxOutput { value: 30, address: carteira.address.clone(), timestamp: 0 },
+            );
+        }
+        let mut tx = carteira.create_transaction("destino", 50, 1, &utxos, &Mempool::new()).unwrap();
+        assert_eq!(tx.inputs.len(), 2);
+
+        let gastos: Vec<TxOutput> = tx.inputs.iter().map(|i| utxos.get(&i.outpoint()).unwrap().clone()).collect();
+        let mut outro_valor = gastos.clone();
+        outro_valor[1].value += 1;
+        assert!(tx.verificar_entrada(0, &gastos));
+        assert!(!tx.verificar_entrada(0, &outro_valor));
+
+        // Com ANYONECANPAY a entrada só responde pelo próprio valor
+        tx.assinar_entrada(0, &gastos, SIGHASH_ALL | SIGHASH_ANYONECANPAY, &sk).unwrap();
+        assert!(tx.verificar_entrada(0, &outro_valor));
+        outro_valor[0].value += 1;
+        assert!(!tx.verificar_entrada(0, &outro_valor));
+
+        assert!(tx.sighash(0, &gastos[..1], SIGHASH_ALL).is_none());
+    }
+}
@@ -1,73 +1,200 @@
-
-
-use serde::{Serialize, Deserialize};
-use sha2::{Sha256, Digest};
-use chrono::prelude::*;
-use crate::transaction::Transaction;
-
-
-/// Estrutura de um bloco da blockchain
-#[derive(Debug, Clone, Serialize, Deserialize)]
-    pub struct Block {
-         pub transactions: Vec<Transaction>,
-    pub index: u64,             // Posição do bloco na cadeia
-    pub timestamp: i64,         // Timestamp Unix
-    pub previous_hash: String,  // Hash do bloco anterior
-    pub hash: String,           // Hash atual do bloco (calculado com PoW)
-    pub nonce: u64,             // Nonce usado na mineração (PoW)
-    pub data: String,           // Conteúdo do bloco (transações em JSON)
-    pub extra_reward: u64,      // Recompensa adicional (ex: regra dos 100 anos)
-}
-
-impl Block {
-    /// Cria novo bloco e o minera com dificuldade
-    pub fn new(index: u64, previous_hash: String, data: String, extra_reward: u64) -> Self {
-        let timestamp = Utc::now().timestamp();
-        let transactions = vec![];
-        let mut block = Block {
-            index,
-            timestamp,
-            previous_hash,
-            hash: String::new(),
-            nonce: 0,
-            data,
-            extra_reward,
-            transactions,
-        };
-        block.mine(4); // 💡 dificuldade fixa 4 (0000...)
-        block
-    }
-
-    /// Calcula o hash SHA256 do bloco (baseado nos dados + nonce)
-    pub fn calculate_hash(&self) -> String {
-        let input = format!(
-            "{}{}{}{}{}{}",
-            self.index,
-            self.timestamp,
-            self.previous_hash,
-            self.nonce,
-            self.data,
-            self.extra_reward
-        );
-        let hash = Sha256::digest(input.as_bytes());
-        format!("{:x}", hash)
-    }
-
-    /// Minera o bloco até encontrar hash com prefixo de zeros conforme dificuldade
-    pub fn mine(&mut self, difficulty: usize) {
-        let prefix = "0".repeat(difficulty);
-        loop {
-            self.hash = self.calculate_hash();
-            if &self.hash[..difficulty] == prefix {
-                break;
-            }
-            self.nonce += 1;
-        }
-    }
-
-    /// Cria o bloco gênesis com mensagem imutável
-    pub fn genesis() -> Self {
-        let message = "No princípio era o Verbo, imutável como VitaBit. alea jacta est";
-        Self::new(0, "0".to_string(), message.to_string(), 0)
-    }
-}
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use chrono::prelude::*;
+use crate::transaction::{OutPoint, ProvaDeVida, Transaction, TxInput};
+use crate::codificacao::codificar;
+use crate::merkle::{raiz_merkle, ProvaMerkle};
+use crate::alvo::{Alvo, BITS_LIMITE};
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Versão atual do formato do cabeçalho
+pub const VERSAO_BLOCO: u32 = 1;
+
+/// Timestamp fixo do gênesis (1º de janeiro de 2025, 00:00 UTC)
+pub const TIMESTAMP_GENESIS: i64 = 1_735_689_600;
+
+/// Nonce que resolve o gênesis no alvo limite
+const NONCE_GENESIS: u64 = 45_805;
+
+/// Hash do gênesis: o mesmo em todos os nós, que só assim compartilham a cadeia
+pub const HASH_GENESIS: &str = "00003ca9f265a2b79c0b8e0cf5c1883f7fd6a108b42643b77e135d0b7773769f";
+
+/// De quantas em quantas tentativas a mineração confere se deve parar
+const TENTATIVAS_POR_CONSULTA: u64 = 4096;
+
+/// Cabeçalho do bloco: é sobre ele que a prova de trabalho é feita
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub previous_hash: String,  // Hash do bloco anterior
+    pub merkle_root: String,    // Raiz de Merkle dos txids do bloco
+    pub reclaimed_hash: String, // SHA256 da lista de outpoints reabsorvidos pelo bloco
+    pub keepalive_hash: String, // SHA256 das provas de vida incluídas no bloco
+    pub extra_reward: u64,      // Emissão do bloco: subsídio + regra dos 100 anos (a coinbase soma ainda as taxas)
+    pub timestamp: i64,         // Timestamp Unix
+    pub bits: u32,              // Alvo de prova de trabalho na forma compacta
+    pub nonce: u64,             // Nonce usado na mineração (PoW); último campo codificado
+}
+
+impl BlockHeader {
+    /// SHA256 da codificação canônica do cabeçalho
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(codificar(self)))
+    }
+}
+
+/// Estrutura de um bloco da blockchain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub index: u64,                     // Posição do bloco na cadeia
+    pub hash: String,                   // Hash do cabeçalho (calculado com PoW)
+    pub transactions: Vec<Transaction>, // Coinbase primeiro
+    pub reclaimed: Vec<OutPoint>,       // Outputs reabsorvidos pela regra dos 100 anos, em ordem
+    pub keepalives: Vec<ProvaDeVida>,   // Provas de vida que renovam outputs sem gastá-los
+}
+
+impl Block {
+    /// Bloco ainda não minerado (nonce zero, sem hash), para um minerador completar
+    pub fn modelo(
+        index: u64,
+        previous_hash: String,
+        transactions: Vec<Transaction>,
+        extra_reward: u64,
+        bits: u32,
+    ) -> Self {
+        let header = BlockHeader {
+            version: VERSAO_BLOCO,
+            previous_hash,
+            merkle_root: Self::raiz_das_transacoes(&transactions),
+            reclaimed_hash: Self::hash_reabsorvidos(&[]),
+            keepalive_hash: Self::hash_provas_de_vida(&[]),
+            extra_reward,
+            timestamp: Utc::now().timestamp(),
+            bits,
+            nonce: 0,
+        };
+        Block {
+            header,
+            index,
+            hash: String::new(),
+            transactions,
+            reclaimed: Vec::new(),
+            keepalives: Vec::new(),
+        }
+    }
+
+    /// Lista no bloco (e compromete no cabeçalho) os outputs que ele reabsorve
+    pub fn reabsorver(&mut self, outpoints: Vec<OutPoint>) {
+        self.header.reclaimed_hash = Self::hash_reabsorvidos(&outpoints);
+        self.reclaimed = outpoints;
+    }
+
+    /// Bytes do corpo do bloco que contam para TAMANHO_MAX_BLOCO: todas as
+    /// transações (coinbase inclusa), as provas de vida e os reabsorvidos
+    pub fn tamanho(&self) -> usize {
+        self.transactions.iter().map(|tx| codificar(tx).len()).sum::<usize>()
+            + self.keepalives.iter().map(|p| codificar(p).len()).sum::<usize>()
+            + self.reclaimed.iter().map(|o| codificar(o).len()).sum::<usize>()
+    }
+
+    /// Hash com que o cabeçalho se compromete com a lista de reabsorvidos
+    pub fn hash_reabsorvidos(outpoints: &[OutPoint]) -> String {
+        format!("{:x}", Sha256::digest(codificar(&outpoints)))
+    }
+
+    /// Inclui no bloco (e compromete no cabeçalho) provas de vida
+    pub fn incluir_provas_de_vida(&mut self, provas: Vec<ProvaDeVida>) {
+        self.header.keepalive_hash = Self::hash_provas_de_vida(&provas);
+        self.keepalives = provas;
+    }
+
+    /// Hash com que o cabeçalho se compromete com as provas de vida
+    pub fn hash_provas_de_vida(provas: &[ProvaDeVida]) -> String {
+        format!("{:x}", Sha256::digest(codificar(&provas)))
+    }
+
+    fn raiz_das_transacoes(transactions: &[Transaction]) -> String {
+        let txids: Vec<String> = transactions.iter().map(|tx| tx.id.clone()).collect();
+        raiz_merkle(&txids)
+    }
+
+    /// Raiz de Merkle recalculada a partir das transações do bloco
+    pub fn calcular_raiz_merkle(&self) -> String {
+        Self::raiz_das_transacoes(&self.transactions)
+    }
+
+    /// Prova de que a transação `txid` está neste bloco
+    pub fn prova_merkle(&self, txid: &str) -> Option<ProvaMerkle> {
+        let txids: Vec<String> = self.transactions.iter().map(|tx| tx.id.clone()).collect();
+        let posicao = txids.iter().position(|t| t == txid)?;
+        ProvaMerkle::gerar(&txids, posicao)
+    }
+
+    /// Calcula o hash SHA256 do bloco (o hash do cabeçalho)
+    pub fn calculate_hash(&self) -> String {
+        self.header.hash()
+    }
+
+    /// Minera o bloco até o hash do cabeçalho atingir o alvo de `header.bits`
+    pub fn mine(&mut self) {
+        let encontrado = self.procurar_nonce(0..u64::MAX, &AtomicBool::new(false), &AtomicU64::new(0));
+        assert!(encontrado, "nenhum nonce de 64 bits atinge o alvo");
+    }
+
+    /// Procura em `nonces` um nonce cujo hash atinja o alvo; se achar, grava nonce e
+    /// hash no bloco. Desiste quando `parar` é ligado e soma em `hashes` as tentativas.
+    pub fn procurar_nonce(&mut self, nonces: Range<u64>, parar: &AtomicBool, hashes: &AtomicU64) -> bool {
+        let alvo = Alvo::de_bits(self.header.bits).expect("bits do cabeçalho não formam um alvo válido");
+        // Só os 8 bytes finais (o nonce) mudam entre tentativas
+        let mut bytes = codificar(&self.header);
+        let inicio_nonce = bytes.len() - 8;
+        let mut tentativas = 0;
+
+        for nonce in nonces {
+            if tentativas == TENTATIVAS_POR_CONSULTA {
+                hashes.fetch_add(tentativas, Ordering::Relaxed);
+                tentativas = 0;
+                if parar.load(Ordering::Relaxed) {
+                    return false;
+                }
+            }
+            tentativas += 1;
+
+            bytes[inicio_nonce..].copy_from_slice(&nonce.to_le_bytes());
+            let hash: [u8; 32] = Sha256::digest(&bytes).into();
+            if alvo.aceita(&hash) {
+                hashes.fetch_add(tentativas, Ordering::Relaxed);
+                self.header.nonce = nonce;
+                self.hash = hex::encode(hash);
+                return true;
+            }
+        }
+
+        hashes.fetch_add(tentativas, Ordering::Relaxed);
+        false
+    }
+
+    /// Bloco gênesis, com mensagem, timestamp e nonce fixos: é idêntico em todos os nós
+    pub fn genesis() -> Self {
+        let message = "No princípio era o Verbo, imutável como VitaBit. alea jacta est";
+        // A mensagem vai na entrada da coinbase, que não paga nada
+        let coinbase = Transaction::new(
+            vec![TxInput {
+                txid: "0".to_string(),
+                index: 0,
+                signature: "coinbase".to_string(),
+                pubkey: message.to_string(),
+            }],
+            vec![],
+        );
+        let mut genesis = Self::modelo(0, "0".to_string(), vec![coinbase], 0, BITS_LIMITE);
+        genesis.header.timestamp = TIMESTAMP_GENESIS;
+        genesis.header.nonce = NONCE_GENESIS;
+        genesis.hash = genesis.calculate_hash();
+        debug_assert_eq!(genesis.hash, HASH_GENESIS);
+        genesis
+    }
+}
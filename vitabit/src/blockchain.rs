@@ -1,237 +1,1095 @@
-const AJUSTE_INTERVALO: usize = 2016;
-const TEMPO_ESPERADO: i64 = 1209600; // 2 semanas em segundos
-
-// src/blockchain.rs
-
-
-use crate::transaction::{TxInput, TxOutput, Transaction};
-use crate::utxo::UTXOSet;
-use crate::block::Block; 
-
-use serde::{Serialize, Deserialize};
-use chrono::Utc;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Blockchain {
-    pub chain: Vec<Block>,
-    pub difficulty: usize,
-    pub total_em_circulacao: u64, // Novo campo para rastrear total em circulação
-}
-
-impl Blockchain {
-
-    pub fn salvar_em_arquivo(&self, caminho: &str) -> std::io::Result<()> {
-        let json = serde_json::to_string_pretty(&self.chain)?;
-        let mut file = File::create(caminho)?;
-        file.write_all(json.as_bytes())?;
-        Ok(())
-    }
-
-    pub fn carregar_de_arquivo(caminho: &str) -> Option<Blockchain> {
-        let mut file = match File::open(caminho) {
-            Ok(f) => f,
-            Err(_) => return None,
-        };
-
-        let mut contents = String::new();
-        if file.read_to_string(&mut contents).is_err() {
-            return None;
-        }
-
-        match serde_json::from_str::<Vec<Block>>(&contents) {
-            Ok(chain) => Some(Blockchain { chain, difficulty: 4, total_em_circulacao: 0 }),
-
-            Err(_) => None,
-        }
-    }
-
-
-    pub fn verify_block(&self, block_index: usize, utxo_set: &UTXOSet) -> bool {
-        if block_index == 0 || block_index >= self.chain.len() {
-            return false;
-        }
-
-        let block = &self.chain[block_index];
-        let previous = &self.chain[block_index - 1];
-
-        if block.previous_hash != previous.hash {
-            return false;
-        }
-
-        if block.hash != block.calculate_hash() {
-            return false;
-        }
-
-        let txs: Vec<Transaction> = match serde_json::from_str(&block.data) {
-            Ok(t) => t,
-            Err(_) => return false,
-        };
-
-        for tx in txs.iter().skip(1) {
-            if !self.verify_transaction(tx, utxo_set) {
-                return false;
-            }
-        }
-
-        true
-    }
-
-    pub fn verify_transaction(&self, tx: &Transaction, utxo_set: &UTXOSet) -> bool {
-        for input in &tx.inputs {
-            let referenced = match utxo_set.utxos.get(&input.txid) {
-                Some(r) => r,
-                None => return false,
-            };
-
-            let output = match referenced.iter().find(|(i, _)| *i == input.index) {
-                Some((_, out)) => out,
-                None => return false,
-            };
-
-            if !input.is_valid(output) {
-                return false;
-            }
-        }
-        true
-    }
-
-    pub fn verify_chain(&self) -> bool {
-        let mut utxo = UTXOSet::new();
-
-        for i in 1..self.chain.len() {
-            if !self.verify_block(i, &utxo) {
-                println!("❌ Bloco #{} inválido", i);
-                return false;
-            }
-
-            utxo = UTXOSet::from_chain_segment(&self.chain[..=i]);
-        }
-
-        println!("✅ Blockchain 100% válida");
-        true
-    }
-
-    pub fn new() -> Self {
-        let genesis = Block::genesis();
-        Blockchain {
-            chain: vec![genesis],
-            difficulty: 4,
-            total_em_circulacao: 0,
-        }
-    }
-
-    pub fn latest_hash(&self) -> String {
-        self.chain.last().unwrap().hash.clone()
-    }
-
-    pub fn height(&self) -> u64 {
-        self.chain.len() as u64
-    }
-
-    pub fn calcular_recompensa(altura_bloco: u64) -> u64 {
-        let recompensa_inicial = 50 * 100_000_000; // 50 VBIT
-        let halvings = altura_bloco / 210_000;
-        let recompensa = recompensa_inicial >> halvings;
-        if recompensa > 0 { recompensa } else { 0 }
-    }
-
-    pub fn calcular_recompensa_extra(
-        utxo_set: &mut UTXOSet,
-        blockchain: &Blockchain,
-        tempo_atual: i64,
-        vbit_em_circulacao: u64
-    ) -> u64 {
-        let inativos = utxo_set.utxos_inativos(blockchain, tempo_atual);
-        let mut total_reabsorvido = 0;
-
-        for (txid, index, output) in inativos {
-            utxo_set.remove(&txid, index);
-            total_reabsorvido += output.value;
-        }
-
-        let limite = 21_000_000 * 100_000_000;
-        if vbit_em_circulacao + total_reabsorvido > limite {
-            0
-        } else if vbit_em_circulacao + total_reabsorvido > limite {
-            limite - vbit_em_circulacao
-        } else {
-            total_reabsorvido
-        }
-    }
-
-    pub fn add_block(&mut self, data: String, miner_address: &str, utxo_set: &mut UTXOSet) -> Block {
-        let tempo_atual = Utc::now().timestamp();
-
-        // Calcula recompensa extra a partir de UTXOs inativos
-        let extra_reward = Blockchain::calcular_recompensa_extra(
-            utxo_set,
-            self,
-            tempo_atual,
-            self.total_em_circulacao,
-        );
-
-        let last_block = self.chain.last().unwrap();
-        let index = last_block.index + 1;
-        let previous_hash = last_block.hash.clone();
-
-        let base_reward = Blockchain::calcular_recompensa(index as u64);
-        let total_reward = base_reward + extra_reward;
-
-        let mut reward_tx = Transaction::new_coinbase(miner_address, total_reward);
-        let mut txs: Vec<Transaction> = serde_json::from_str(&data).unwrap_or_default();
-        txs.insert(0, reward_tx);
-
-        let txs_json = serde_json::to_string(&txs).unwrap();
-        let new_block = Block::new(index, previous_hash, txs_json, total_reward);
-        self.chain.push(new_block.clone());
-
-        self.total_em_circulacao += total_reward;
-        self.ajustar_dificuldade();
-
-        new_block
-    }
-
-    pub fn ajustar_dificuldade(&mut self) {
-        let altura = self.chain.len();
-
-        if altura % AJUSTE_INTERVALO != 0 || altura <= AJUSTE_INTERVALO {
-            return;
-        }
-
-        let bloco_atual = &self.chain[altura - 1];
-        let bloco_ajuste = &self.chain[altura - AJUSTE_INTERVALO];
-        let tempo_real = bloco_atual.timestamp - bloco_ajuste.timestamp;
-
-        if tempo_real < TEMPO_ESPERADO / 2 {
-            self.difficulty += 1;
-            println!("⏫ Aumentando dificuldade para {}", self.difficulty);
-        } else if tempo_real > TEMPO_ESPERADO * 2 {
-            self.difficulty = self.difficulty.saturating_sub(1);
-            println!("⏬ Reduzindo dificuldade para {}", self.difficulty);
-        } else {
-            println!("🔁 Mantendo dificuldade em {}", self.difficulty);
-        }
-    }
-
-    pub fn is_valid(&self) -> bool {
-        for i in 1..self.chain.len() {
-            let current = &self.chain[i];
-            let previous = &self.chain[i - 1];
-
-            if current.hash != current.calculate_hash() {
-                return false;
-            }
-
-            if current.previous_hash != previous.hash {
-                return false;
-            }
-        }
-        true
-    }
-
-    pub fn get_blocks(&self) -> &Vec<Block> {
-        &self.chain
-    }
-}
+const AJUSTE_INTERVALO: u64 = 2016;
+const TEMPO_ESPERADO: i64 = 1209600; // 2 semanas em segundos
+
+/// Intervalo alvo entre blocos, em segundos (10 minutos)
+pub const TEMPO_POR_BLOCO: i64 = TEMPO_ESPERADO / AJUSTE_INTERVALO as i64;
+
+/// Espaço máximo (em bytes) do corpo de um bloco: transações, coinbase inclusa,
+/// provas de vida e reabsorvidos (ver `Block::tamanho`)
+pub const TAMANHO_MAX_BLOCO: usize = 1_000_000;
+
+/// Arquivo JSON usado antes do armazém de blocos; seus blocos não têm cabeçalho e não são importados
+pub const ARQUIVO_BLOCKCHAIN_LEGADO: &str = "blockchain.json";
+
+/// A cada quantos blocos a fotografia do UTXOSet é regravada
+const INTERVALO_SNAPSHOT: u64 = 100;
+
+/// Blocos anteriores cuja mediana de timestamps o próximo bloco precisa superar
+pub const BLOCOS_TEMPO_MEDIANO: usize = 11;
+
+/// Quanto o timestamp de um bloco pode estar à frente da hora da rede (2 horas)
+pub const MAX_TEMPO_FUTURO: i64 = 2 * 60 * 60;
+
+// src/blockchain.rs
+
+
+use crate::transaction::{ProvaDeVida, Transaction};
+use crate::utxo::{DesfazerBloco, UTXOSet, ARQUIVO_UTXOS};
+use crate::block::{Block, HASH_GENESIS};
+use crate::armazem::{ArmazemBlocos, EntradaIndice};
+use crate::codificacao::codificar;
+use crate::alvo::Alvo;
+use crate::relogio::{Relogio, TempoRede};
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct Blockchain {
+    pub total_em_circulacao: u64, // Novo campo para rastrear total em circulação
+    cadeia: Vec<String>,             // hashes da cadeia ativa (ramo de maior trabalho), por altura
+    trabalho: HashMap<String, u128>, // árvore de blocos válidos conhecidos: hash → trabalho acumulado
+    armazem: ArmazemBlocos,          // os blocos em si, lidos sob demanda
+    utxos: UTXOSet,                  // outputs não gastos no topo da cadeia ativa
+    diretorio: Option<PathBuf>,      // onde gravar a fotografia dos UTXOs (nenhum: só memória)
+    tempo: Arc<TempoRede>,           // hora da rede, limite para o timestamp dos blocos
+}
+
+/// Efeito de um bloco aceito sobre a cadeia ativa
+#[derive(Debug, Default)]
+pub struct MudancaCadeia {
+    pub conectados: Vec<Block>,                // blocos que passaram a fazer parte da cadeia ativa
+    pub transacoes_confirmadas: Vec<Transaction>,
+    pub transacoes_orfas: Vec<Transaction>,    // de blocos desconectados numa reorganização
+    pub provas_orfas: Vec<ProvaDeVida>,        // idem, para as provas de vida
+    pub invalido: Option<String>,              // motivo, se o fim do novo ramo foi descartado por inválido
+}
+
+/// Motivo de um bloco não ter sido aceito; só `Invalido` é culpa de quem o enviou
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErroBloco {
+    JaConhecido,
+    AnteriorDesconhecido(String), // o pai ainda não chegou: falta sincronizar
+    NoFuturo { timestamp: i64, limite: i64 }, // adiantado demais por ora; pode valer mais tarde
+    Invalido(String),             // viola as regras de consenso
+    Armazem(String),              // falha local ao ler ou gravar blocos
+}
+
+impl fmt::Display for ErroBloco {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErroBloco::JaConhecido => write!(f, "bloco já conhecido"),
+            ErroBloco::AnteriorDesconhecido(hash) => write!(f, "bloco anterior {} desconhecido", hash),
+            ErroBloco::NoFuturo { timestamp, limite } => write!(
+                f,
+                "timestamp {} mais de {}s à frente da hora da rede (limite {})",
+                timestamp, MAX_TEMPO_FUTURO, limite
+            ),
+            ErroBloco::Invalido(motivo) => write!(f, "{}", motivo),
+            ErroBloco::Armazem(motivo) => write!(f, "falha no armazém de blocos: {}", motivo),
+        }
+    }
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Blockchain {
+
+    fn com_armazem(armazem: ArmazemBlocos) -> Self {
+        Blockchain {
+            total_em_circulacao: 0,
+            cadeia: Vec::new(),
+            trabalho: HashMap::new(),
+            armazem,
+            utxos: UTXOSet::new(),
+            diretorio: None,
+            tempo: Arc::new(TempoRede::new()),
+        }
+    }
+
+    /// Abre a blockchain guardada em `diretorio`; na primeira abertura começa pelo gênesis
+    pub fn abrir(diretorio: &Path) -> Result<Blockchain, String> {
+        let armazem = ArmazemBlocos::abrir(diretorio)
+            .map_err(|e| format!("falha ao abrir o armazém de blocos: {}", e))?;
+        let mut bc = Blockchain::com_armazem(armazem);
+        bc.diretorio = Some(diretorio.to_path_buf());
+
+        if bc.armazem.is_empty() {
+            if diretorio.join(ARQUIVO_BLOCKCHAIN_LEGADO).exists() {
+                println!("⚠️ {} está no formato antigo, sem cabeçalho de bloco, e foi ignorado", ARQUIVO_BLOCKCHAIN_LEGADO);
+            }
+            bc.conectar_no_topo(&Block::genesis())?;
+            bc.salvar_utxos()?;
+            return Ok(bc);
+        }
+
+        // Remonta a árvore pelo índice: pais são sempre gravados antes dos filhos
+        for entrada in bc.armazem.entradas() {
+            let anterior = if entrada.hash == HASH_GENESIS {
+                0
+            } else {
+                match bc.trabalho.get(&entrada.previous_hash) {
+                    Some(t) => *t,
+                    None => continue,
+                }
+            };
+            bc.trabalho.insert(entrada.hash.clone(), anterior + trabalho_da_dificuldade(entrada.bits));
+        }
+        if !bc.trabalho.contains_key(HASH_GENESIS) {
+            return Err(format!(
+                "armazém de blocos em {} foi criado com outro gênesis; apague-o para sincronizar com a rede",
+                diretorio.display()
+            ));
+        }
+
+        // Topo registrado; sem ele, o bloco de maior trabalho acumulado
+        let topo = bc
+            .armazem
+            .topo()
+            .filter(|t| bc.trabalho.contains_key(*t))
+            .map(|t| t.to_string())
+            .or_else(|| {
+                bc.trabalho
+                    .iter()
+                    .max_by_key(|(_, t)| **t)
+                    .map(|(h, _)| h.clone())
+            })
+            .ok_or("armazém de blocos sem gênesis")?;
+
+        let mut cursor = topo;
+        loop {
+            let entrada = bc.armazem.entrada(&cursor).ok_or("índice de blocos incompleto")?;
+            bc.total_em_circulacao += emissao_nova(entrada.index, entrada.extra_reward);
+            bc.cadeia.push(cursor.clone());
+            if entrada.index == 0 {
+                break;
+            }
+            cursor = entrada.previous_hash.clone();
+        }
+        bc.cadeia.reverse();
+
+        bc.carregar_utxos()?;
+        Ok(bc)
+    }
+
+    /// Parte da fotografia do UTXOSet e só processa os blocos que mudaram desde
+    /// ela; sem fotografia utilizável, processa a cadeia ativa inteira
+    fn carregar_utxos(&mut self) -> Result<(), String> {
+        let caminho = self.diretorio.as_ref().map(|d| d.join(ARQUIVO_UTXOS));
+        let reprocessados = match caminho.as_deref().and_then(UTXOSet::carregar_snapshot) {
+            Some((topo, utxos)) => match self.atualizar_utxos(utxos, Some(topo)) {
+                Ok(n) => n,
+                Err(e) => {
+                    println!("⚠️ Fotografia dos UTXOs descartada: {}", e);
+                    self.atualizar_utxos(UTXOSet::new(), None)?
+                }
+            },
+            None => self.atualizar_utxos(UTXOSet::new(), None)?,
+        };
+
+        if reprocessados > 0 {
+            println!("🔁 UTXOs atualizados com {} bloco(s)", reprocessados);
+            self.salvar_utxos()?;
+        }
+        Ok(())
+    }
+
+    /// Leva `utxos`, que reflete o bloco `topo` (nenhum: antes do gênesis), até o
+    /// topo da cadeia ativa. Devolve quantos blocos foram conectados.
+    fn atualizar_utxos(&mut self, mut utxos: UTXOSet, topo: Option<String>) -> Result<usize, String> {
+        // Desfaz os blocos que saíram da cadeia ativa desde a fotografia
+        let mut cursor = topo;
+        while let Some(hash) = cursor.clone() {
+            let (index, anterior) = match self.armazem.entrada(&hash) {
+                Some(e) => (e.index, e.previous_hash.clone()),
+                None => return Err(format!("bloco {} desconhecido", hash)),
+            };
+            if self.cadeia.get(index as usize) == Some(&hash) {
+                break;
+            }
+            let block = self.armazem.ler(&hash).map_err(|e| e.to_string())?;
+            let desfazer = self.armazem.ler_desfazer(&hash).map_err(|e| e.to_string())?;
+            utxos.desconectar_bloco(&block, &desfazer);
+            cursor = (index > 0).then_some(anterior);
+        }
+
+        let inicio = match &cursor {
+            Some(hash) => self.armazem.entrada(hash).map(|e| e.index as usize + 1).unwrap_or(0),
+            None => 0,
+        };
+        for altura in inicio..self.cadeia.len() {
+            let hash = self.cadeia[altura].clone();
+            let block = self.armazem.ler(&hash).map_err(|e| e.to_string())?;
+            let desfazer = utxos.conectar_bloco(&block);
+            self.gravar_desfazer(&hash, &desfazer)?;
+        }
+
+        self.utxos = utxos;
+        Ok(self.cadeia.len() - inicio)
+    }
+
+    /// Grava a fotografia do UTXOSet no topo atual
+    pub fn salvar_utxos(&self) -> Result<(), String> {
+        let Some(diretorio) = &self.diretorio else {
+            return Ok(());
+        };
+        self.utxos
+            .salvar_snapshot(&diretorio.join(ARQUIVO_UTXOS), &self.latest_hash())
+            .map_err(|e| format!("falha ao gravar {}: {}", ARQUIVO_UTXOS, e))
+    }
+
+    /// Outputs não gastos no topo da cadeia ativa
+    pub fn utxos(&self) -> &UTXOSet {
+        &self.utxos
+    }
+
+    /// Monta a blockchain (e a árvore de blocos) a partir de uma cadeia linear, só em memória
+    pub fn from_chain(chain: Vec<Block>) -> Self {
+        let mut bc = Blockchain::com_armazem(ArmazemBlocos::em_memoria());
+        for block in &chain {
+            bc.conectar_no_topo(block).expect("armazém em memória não falha");
+        }
+        bc
+    }
+
+    /// Hora ajustada pela rede usada na validação e na montagem de blocos
+    pub fn tempo_rede(&self) -> &Arc<TempoRede> {
+        &self.tempo
+    }
+
+    /// Troca o relógio local (por exemplo, por um `RelogioManual` nos testes);
+    /// os desvios já informados pelos peers são descartados
+    pub fn usar_relogio(&mut self, relogio: Arc<dyn Relogio>) {
+        self.tempo = Arc::new(TempoRede::com_relogio(relogio));
+    }
+
+    /// Mediana dos timestamps dos últimos blocos da cadeia ativa; o próximo
+    /// bloco precisa de um timestamp maior que ela
+    pub fn tempo_mediano(&self) -> i64 {
+        self.tempo_mediano_apos(&self.latest_hash())
+            .expect("topo da cadeia ativa está no índice")
+    }
+
+    /// Mediana dos timestamps de `anterior` e dos seus antecessores, até
+    /// BLOCOS_TEMPO_MEDIANO blocos (menos perto do gênesis)
+    fn tempo_mediano_apos(&self, anterior: &str) -> Option<i64> {
+        let mut tempos = Vec::with_capacity(BLOCOS_TEMPO_MEDIANO);
+        let mut entrada = self.armazem.entrada(anterior)?;
+        loop {
+            tempos.push(entrada.timestamp);
+            if tempos.len() == BLOCOS_TEMPO_MEDIANO || entrada.index == 0 {
+                break;
+            }
+            entrada = self.armazem.entrada(&entrada.previous_hash)?;
+        }
+        tempos.sort_unstable();
+        Some(tempos[tempos.len() / 2])
+    }
+
+    /// Alvo compacto (bits) exigido do próximo bloco da cadeia ativa
+    pub fn dificuldade(&self) -> u32 {
+        self.dificuldade_apos(&self.latest_hash())
+            .expect("topo da cadeia ativa está no índice")
+    }
+
+    /// Alvo exigido de um filho de `anterior`, calculado só pela história do
+    /// ramo dele: repete o do pai e é reajustado a cada AJUSTE_INTERVALO blocos,
+    /// na proporção do tempo que o período anterior levou
+    fn dificuldade_apos(&self, anterior: &str) -> Option<u32> {
+        let pai = self.armazem.entrada(anterior)?;
+        let altura = pai.index + 1;
+        if !altura.is_multiple_of(AJUSTE_INTERVALO) {
+            return Some(pai.bits);
+        }
+
+        let mut primeiro = pai;
+        for _ in 1..AJUSTE_INTERVALO {
+            primeiro = self.armazem.entrada(&primeiro.previous_hash)?;
+        }
+        let tempo_real = pai.timestamp - primeiro.timestamp;
+
+        let alvo = Alvo::de_bits(pai.bits)?;
+        Some(alvo.ajustar(tempo_real, TEMPO_ESPERADO).para_bits())
+    }
+
+    /// Grava o bloco no armazém e o insere na árvore, acumulando o trabalho do seu pai
+    fn registrar_bloco(&mut self, block: &Block) -> Result<(), String> {
+        self.armazem
+            .gravar(block)
+            .map_err(|e| format!("falha ao gravar o bloco #{}: {}", block.index, e))?;
+        let anterior = self.trabalho.get(&block.header.previous_hash).copied().unwrap_or(0);
+        self.trabalho
+            .insert(block.hash.clone(), anterior + trabalho_da_dificuldade(block.header.bits));
+        Ok(())
+    }
+
+    /// Registra o bloco e o põe no topo da cadeia ativa, sem validação
+    fn conectar_no_topo(&mut self, block: &Block) -> Result<(), String> {
+        self.registrar_bloco(block)?;
+        let desfazer = self.utxos.conectar_bloco(block);
+        self.fixar_no_topo(block, &desfazer)
+    }
+
+    /// Põe no topo da cadeia ativa o bloco já registrado e conectado aos UTXOs.
+    /// Se a gravação falhar, o bloco é desconectado e a cadeia fica como estava.
+    fn fixar_no_topo(&mut self, block: &Block, desfazer: &DesfazerBloco) -> Result<(), String> {
+        if let Err(e) = self.gravar_desfazer(&block.hash, desfazer) {
+            self.utxos.desconectar_bloco(block, desfazer);
+            return Err(e);
+        }
+        self.cadeia.push(block.hash.clone());
+        self.total_em_circulacao += emissao_nova(block.index, block.header.extra_reward);
+        if let Err(e) = self.marcar_topo() {
+            self.cadeia.pop();
+            self.total_em_circulacao -= emissao_nova(block.index, block.header.extra_reward);
+            self.utxos.desconectar_bloco(block, desfazer);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn gravar_desfazer(&mut self, hash: &str, desfazer: &DesfazerBloco) -> Result<(), String> {
+        self.armazem
+            .gravar_desfazer(hash, desfazer)
+            .map_err(|e| format!("falha ao gravar o registro de desfazer de {}: {}", hash, e))
+    }
+
+    /// Grava no índice o topo atual da cadeia ativa, fotografando os UTXOs de tempos em tempos
+    fn marcar_topo(&mut self) -> Result<(), String> {
+        let topo = self.latest_hash();
+        self.armazem
+            .marcar_topo(&topo)
+            .map_err(|e| format!("falha ao gravar o topo da cadeia: {}", e))?;
+
+        if self.height().is_multiple_of(INTERVALO_SNAPSHOT) {
+            if let Err(e) = self.salvar_utxos() {
+                println!("⚠️ {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Trabalho acumulado da cadeia ativa
+    pub fn trabalho_acumulado(&self) -> u128 {
+        self.trabalho.get(&self.latest_hash()).copied().unwrap_or(0)
+    }
+
+    pub fn conhece_bloco(&self, hash: &str) -> bool {
+        self.trabalho.contains_key(hash)
+    }
+
+    /// Busca um bloco conhecido (da cadeia ativa ou de ramos laterais) pelo hash
+    pub fn bloco_por_hash(&self, hash: &str) -> Option<Block> {
+        if !self.conhece_bloco(hash) {
+            return None;
+        }
+        match self.armazem.ler(hash) {
+            Ok(block) => Some(block),
+            Err(e) => {
+                println!("❌ Falha ao ler o bloco {} do armazém: {}", hash, e);
+                None
+            }
+        }
+    }
+
+    /// Hash do bloco da cadeia ativa na altura informada
+    pub fn hash_na_altura(&self, altura: u64) -> Option<&str> {
+        self.cadeia.get(altura as usize).map(|h| h.as_str())
+    }
+
+    /// Bloco da cadeia ativa na altura informada
+    pub fn bloco_na_altura(&self, altura: u64) -> Option<Block> {
+        self.bloco_por_hash(self.hash_na_altura(altura)?)
+    }
+
+    /// Localizador de blocos: hashes da cadeia ativa do topo para trás,
+    /// densos no início e com passo dobrando depois, terminando no gênesis
+    pub fn localizador(&self) -> Vec<String> {
+        let mut hashes = Vec::new();
+        let mut passo = 1;
+        let mut altura = self.cadeia.len() - 1;
+
+        loop {
+            hashes.push(self.cadeia[altura].clone());
+            if altura == 0 {
+                break;
+            }
+            if hashes.len() >= 10 {
+                passo *= 2;
+            }
+            altura = altura.saturating_sub(passo);
+        }
+
+        hashes
+    }
+
+    /// Entradas da cadeia ativa posteriores ao primeiro hash do localizador
+    /// que pertence a ela (ou ao gênesis, se nenhum pertencer)
+    pub fn entradas_apos(&self, localizador: &[String], limite: usize) -> Vec<&EntradaIndice> {
+        let inicio = localizador
+            .iter()
+            .find_map(|hash| {
+                let altura = self.armazem.entrada(hash)?.index as usize;
+                (self.cadeia.get(altura) == Some(hash)).then_some(altura)
+            })
+            .map(|pos| pos + 1)
+            .unwrap_or(1);
+
+        let fim = (inicio + limite).min(self.cadeia.len());
+        self.cadeia[inicio.min(fim)..fim]
+            .iter()
+            .filter_map(|h| self.armazem.entrada(h))
+            .collect()
+    }
+
+
+    pub fn verify_block(&self, block_index: usize, utxo_set: &mut UTXOSet) -> bool {
+        if block_index == 0 || block_index >= self.cadeia.len() {
+            return false;
+        }
+
+        let (Some(block), Some(previous)) = (
+            self.bloco_na_altura(block_index as u64),
+            self.bloco_na_altura(block_index as u64 - 1),
+        ) else {
+            return false;
+        };
+
+        self.validar_bloco(&block, &previous, utxo_set).is_ok()
+    }
+
+    /// Aplica as regras de consenso a um bloco que estende `previous`,
+    /// retornando o motivo da rejeição quando inválido
+    /// (`utxo_set` termina como começou)
+    pub fn validar_bloco(&self, block: &Block, previous: &Block, utxo_set: &mut UTXOSet) -> Result<(), String> {
+        let desfazer = self.conectar_validado(block, previous, utxo_set)?;
+        utxo_set.desconectar_bloco(block, &desfazer);
+        Ok(())
+    }
+
+    /// Valida o bloco conectando-o a `utxo_set` etapa por etapa, sem copiar o
+    /// conjunto. Se ele passar, fica conectado e o registro para desfazê-lo é
+    /// devolvido; se não, o conjunto volta ao estado de antes.
+    pub fn conectar_validado(
+        &self,
+        block: &Block,
+        previous: &Block,
+        utxo_set: &mut UTXOSet,
+    ) -> Result<DesfazerBloco, String> {
+        if block.header.previous_hash != previous.hash {
+            return Err(format!("previous_hash {} não aponta para {}", block.header.previous_hash, previous.hash));
+        }
+
+        self.validar_estrutura(block, previous)?;
+
+        let txs = &block.transactions;
+        let coinbase = txs.first().ok_or("bloco sem coinbase")?;
+        match coinbase.inputs.as_slice() {
+            [entrada] if entrada.signature == "coinbase" && entrada.txid == "0" => {
+                if entrada.index as u64 != block.index {
+                    return Err(format!("coinbase declara altura {} em bloco #{}", entrada.index, block.index));
+                }
+            }
+            _ => return Err("primeira transação não é coinbase".to_string()),
+        }
+
+        // O bloco lista exatamente os outputs que a regra dos 100 anos reabsorve
+        // nessa altura, e a emissão é o subsídio mais o valor deles
+        let reabsorviveis = utxo_set.reabsorviveis(block.index);
+        if !block.reclaimed.iter().eq(reabsorviveis.iter().map(|(outpoint, _)| outpoint)) {
+            return Err(format!(
+                "bloco lista {} outputs reabsorvidos, mas a regra dos 100 anos reabsorve {}",
+                block.reclaimed.len(),
+                reabsorviveis.len()
+            ));
+        }
+        let reabsorvido = reabsorviveis
+            .iter()
+            .try_fold(0u64, |acc, (_, out)| acc.checked_add(out.value))
+            .ok_or("overflow na soma dos reabsorvidos")?;
+        let emissao = Blockchain::calcular_recompensa(block.index)
+            .checked_add(reabsorvido)
+            .ok_or("overflow na emissão do bloco")?;
+        if block.header.extra_reward != emissao {
+            return Err(format!(
+                "emissão de {} diferente da permitida ({} de subsídio + {} reabsorvidos)",
+                block.header.extra_reward,
+                emissao - reabsorvido,
+                reabsorvido
+            ));
+        }
+
+        let mut desfazer = DesfazerBloco::default();
+        let mut conectadas = 0;
+        match Self::conectar_etapas(block, utxo_set, &mut desfazer, &mut conectadas) {
+            Ok(()) => {
+                utxo_set.concluir_conexao(block.index);
+                Ok(desfazer)
+            }
+            Err(e) => {
+                utxo_set.desfazer_conexao(&txs[..conectadas], block.index, &desfazer);
+                Err(e)
+            }
+        }
+    }
+
+    /// Conecta o bloco validando cada prova de vida e cada transação contra o
+    /// conjunto do jeito que as anteriores o deixaram. Para no primeiro erro,
+    /// com `conectadas` transações já aplicadas.
+    fn conectar_etapas(
+        block: &Block,
+        utxos: &mut UTXOSet,
+        desfazer: &mut DesfazerBloco,
+        conectadas: &mut usize,
+    ) -> Result<(), String> {
+        // Os reabsorvidos saem antes das provas de vida e das transações do bloco
+        utxos.reabsorver(&block.reclaimed, desfazer);
+
+        let mut renovados = HashSet::new();
+        for prova in &block.keepalives {
+            if !prova.outpoints.iter().all(|o| renovados.insert(o)) {
+                return Err(format!("prova de vida {} renova output já renovado no bloco", prova.id()));
+            }
+            prova
+                .validar(utxos, block.index)
+                .map_err(|e| format!("prova de vida {} inválida: {}", prova.id(), e))?;
+            utxos.renovar(prova, block.index, desfazer);
+        }
+
+        let (coinbase, txs) = block.transactions.split_first().ok_or("bloco sem coinbase")?;
+        utxos.conectar_transacao(coinbase, true, block.index, desfazer);
+        *conectadas = 1;
+
+        let mut taxas: u64 = 0;
+        for tx in txs {
+            let taxa = tx
+                .validar(utxos)
+                .map_err(|e| format!("transação {} inválida: {}", tx.id, e))?;
+            taxas = taxas.checked_add(taxa).ok_or("overflow na soma das taxas")?;
+            utxos.conectar_transacao(tx, false, block.index, desfazer);
+            *conectadas += 1;
+        }
+
+        let valor_coinbase = coinbase
+            .outputs
+            .iter()
+            .try_fold(0u64, |acc, o| acc.checked_add(o.value))
+            .ok_or("overflow na soma da coinbase")?;
+        let esperado = block.header.extra_reward.checked_add(taxas).ok_or("overflow na recompensa do bloco")?;
+        if valor_coinbase != esperado {
+            return Err(format!(
+                "coinbase paga {} mas deveria pagar {} (emissão {} + taxas {})",
+                valor_coinbase, esperado, block.header.extra_reward, taxas
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Regras que não dependem do conjunto de UTXOs: posição, tempo mediano, prova
+    /// de trabalho e compromisso do cabeçalho com todo o corpo do bloco (nenhum
+    /// campo fora do cabeçalho pode mudar sem mudar o hash)
+    fn validar_estrutura(&self, block: &Block, previous: &Block) -> Result<(), String> {
+        if block.index != previous.index + 1 {
+            return Err(format!("índice {} não sucede {}", block.index, previous.index));
+        }
+
+        if block.hash != block.calculate_hash() {
+            return Err("hash não confere com o cabeçalho do bloco".to_string());
+        }
+
+        let mediana = self
+            .tempo_mediano_apos(&previous.hash)
+            .ok_or_else(|| format!("bloco anterior {} fora do índice", previous.hash))?;
+        if block.header.timestamp <= mediana {
+            return Err(format!(
+                "timestamp {} não supera a mediana dos {} blocos anteriores ({})",
+                block.header.timestamp, BLOCOS_TEMPO_MEDIANO, mediana
+            ));
+        }
+        let exigida = self
+            .dificuldade_apos(&previous.hash)
+            .ok_or_else(|| format!("bloco anterior {} fora do índice", previous.hash))?;
+        if block.header.bits != exigida {
+            return Err(format!("bits {:08x} diferentes dos exigidos ({:08x})", block.header.bits, exigida));
+        }
+
+        let alvo = Alvo::de_bits(exigida).ok_or_else(|| format!("bits {:08x} não formam um alvo", exigida))?;
+        if !alvo.aceita_hex(&block.hash) {
+            return Err(format!("prova de trabalho insuficiente (bits {:08x})", exigida));
+        }
+
+        let tamanho = block.tamanho();
+        if tamanho > TAMANHO_MAX_BLOCO {
+            return Err(format!("bloco com {} bytes, acima do máximo de {}", tamanho, TAMANHO_MAX_BLOCO));
+        }
+
+        // Uma transação só pode gastar outputs de transações anteriores a ela no bloco
+        let posicoes: HashMap<&str, usize> = block
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(i, tx)| (tx.id.as_str(), i))
+            .collect();
+        for (i, tx) in block.transactions.iter().enumerate() {
+            if tx.inputs.iter().any(|input| posicoes.get(input.txid.as_str()).is_some_and(|&p| p >= i)) {
+                return Err(format!("transação {} gasta output criado depois dela no bloco", tx.id));
+            }
+        }
+
+        // A raiz de Merkle cobre os txids; cada um precisa ser o hash da sua transação
+        if let Some(tx) = block.transactions.iter().find(|tx| !tx.id_confere()) {
+            return Err(format!("transação {} com txid que não confere com o conteúdo", tx.id));
+        }
+        if block.header.merkle_root != block.calcular_raiz_merkle() {
+            return Err("raiz de Merkle não confere com as transações".to_string());
+        }
+
+        if block.header.reclaimed_hash != Block::hash_reabsorvidos(&block.reclaimed) {
+            return Err("cabeçalho não confere com a lista de reabsorvidos".to_string());
+        }
+
+        if block.header.keepalive_hash != Block::hash_provas_de_vida(&block.keepalives) {
+            return Err("cabeçalho não confere com as provas de vida".to_string());
+        }
+
+        // Txids repetidos gerariam a mesma raiz para listas diferentes
+        let mut txids = HashSet::new();
+        if !block.transactions.iter().all(|tx| txids.insert(&tx.id)) {
+            return Err("transação repetida no bloco".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Valida um bloco recebido da rede e o insere na árvore de blocos.
+    /// Se o ramo dele passar a ter o maior trabalho acumulado, a cadeia ativa
+    /// é reorganizada.
+    pub fn aceitar_bloco(&mut self, block: Block) -> Result<MudancaCadeia, ErroBloco> {
+        if self.conhece_bloco(&block.hash) {
+            return Err(ErroBloco::JaConhecido);
+        }
+
+        // Não é regra de consenso: o mesmo bloco passa quando o relógio alcançá-lo
+        let limite = self.tempo.agora() + MAX_TEMPO_FUTURO;
+        if block.header.timestamp > limite {
+            return Err(ErroBloco::NoFuturo { timestamp: block.header.timestamp, limite });
+        }
+
+        let previous = self
+            .bloco_por_hash(&block.header.previous_hash)
+            .ok_or_else(|| ErroBloco::AnteriorDesconhecido(block.header.previous_hash.clone()))?;
+
+        // Estende o topo: caminho comum, sem reorganização
+        if block.header.previous_hash == self.latest_hash() {
+            let mut utxos = std::mem::take(&mut self.utxos);
+            let conectado = self.conectar_validado(&block, &previous, &mut utxos);
+            self.utxos = utxos;
+            let desfazer = conectado.map_err(ErroBloco::Invalido)?;
+            if let Err(e) = self.registrar_bloco(&block) {
+                self.utxos.desconectar_bloco(&block, &desfazer);
+                return Err(ErroBloco::Armazem(e));
+            }
+            // Se falhar, fixar_no_topo já devolve os UTXOs ao topo anterior
+            self.fixar_no_topo(&block, &desfazer).map_err(ErroBloco::Armazem)?;
+
+            let txs = block.transactions.clone();
+
+            return Ok(MudancaCadeia {
+                conectados: vec![block],
+                transacoes_confirmadas: txs,
+                transacoes_orfas: Vec::new(),
+                provas_orfas: Vec::new(),
+                invalido: None,
+            });
+        }
+
+        // Ramo lateral: só as regras que não dependem do conjunto de UTXOs
+        self.validar_estrutura(&block, &previous).map_err(ErroBloco::Invalido)?;
+
+        self.registrar_bloco(&block).map_err(ErroBloco::Armazem)?;
+
+        if self.trabalho[&block.hash] <= self.trabalho_acumulado() {
+            println!("🌿 Bloco #{} guardado em ramo lateral", block.index);
+            return Ok(MudancaCadeia::default());
+        }
+
+        self.reorganizar(&block.hash)
+    }
+
+    /// Troca a cadeia ativa pelo ramo que termina em `novo_topo`, desfazendo os
+    /// blocos após o ponto de bifurcação e conectando os do novo ramo. Um bloco
+    /// inválido no ramo é descartado com seus descendentes, e a troca vai só até
+    /// o bloco anterior a ele, se esse trecho ainda tiver mais trabalho.
+    fn reorganizar(&mut self, novo_topo: &str) -> Result<MudancaCadeia, ErroBloco> {
+        // Sobe pelo novo ramo até encontrar um bloco da cadeia ativa
+        let mut ramo = Vec::new();
+        let mut cursor = novo_topo.to_string();
+        let bifurcacao = loop {
+            let block = self
+                .bloco_por_hash(&cursor)
+                .ok_or_else(|| ErroBloco::Armazem(format!("bloco {} do novo ramo ilegível", cursor)))?;
+            let idx = block.index as usize;
+            if self.cadeia.get(idx) == Some(&cursor) {
+                break idx;
+            }
+            cursor = block.header.previous_hash.clone();
+            ramo.push(block);
+        };
+        ramo.reverse();
+
+        // Volta o conjunto de UTXOs ao ponto de bifurcação com os registros de desfazer
+        let mut utxos = self.utxos.clone();
+        let mut desconectados = Vec::new();
+        for hash in self.cadeia[bifurcacao + 1..].iter().rev() {
+            let block = self
+                .bloco_por_hash(hash)
+                .ok_or_else(|| ErroBloco::Armazem(format!("bloco {} da cadeia ativa ilegível", hash)))?;
+            let desfazer = self
+                .armazem
+                .ler_desfazer(hash)
+                .map_err(|e| ErroBloco::Armazem(format!("bloco #{} sem como ser desfeito: {}", block.index, e)))?;
+            utxos.desconectar_bloco(&block, &desfazer);
+            desconectados.push(block);
+        }
+        desconectados.reverse();
+
+        // E avança pelo novo ramo
+        let ponto = self
+            .bloco_na_altura(bifurcacao as u64)
+            .ok_or_else(|| ErroBloco::Armazem("bloco da bifurcação ilegível".to_string()))?;
+        let mut registros = Vec::new();
+        let mut falha = None;
+        for (i, block) in ramo.iter().enumerate() {
+            let previous = if i == 0 { &ponto } else { &ramo[i - 1] };
+            match self.conectar_validado(block, previous, &mut utxos) {
+                Ok(desfazer) => registros.push(desfazer),
+                Err(e) => {
+                    falha = Some((i, format!("bloco #{} do novo ramo inválido: {}", block.index, e)));
+                    break;
+                }
+            }
+        }
+
+        let mut invalido = None;
+        if let Some((i, motivo)) = falha {
+            // O ramo é inválido a partir daqui: descarta o bloco e seus descendentes
+            for descartado in ramo.drain(i..) {
+                self.trabalho.remove(&descartado.hash);
+                if let Err(e) = self.armazem.marcar_invalido(&descartado.hash) {
+                    println!("⚠️ Falha ao marcar o bloco {} como inválido: {}", descartado.hash, e);
+                }
+            }
+
+            // Os blocos válidos antes dele ainda valem a troca se somarem mais trabalho
+            let mais_pesado = ramo.last().is_some_and(|b| self.trabalho[&b.hash] > self.trabalho_acumulado());
+            if !mais_pesado {
+                return Err(ErroBloco::Invalido(format!("reorganização abortada: {}", motivo)));
+            }
+            println!("⚠️ {}; reorganizando só até o bloco #{}", motivo, ramo[i - 1].index);
+            invalido = Some(motivo);
+        }
+
+        self.cadeia.truncate(bifurcacao + 1);
+        println!(
+            "🔀 Reorganização: {} bloco(s) desconectado(s), {} conectado(s) a partir do #{}",
+            desconectados.len(),
+            ramo.len(),
+            bifurcacao
+        );
+
+        let mut mudanca = MudancaCadeia { invalido, ..MudancaCadeia::default() };
+        for block in &desconectados {
+            self.total_em_circulacao -= emissao_nova(block.index, block.header.extra_reward);
+            mudanca.transacoes_orfas.extend(block.transactions.iter().skip(1).cloned());
+            mudanca.provas_orfas.extend(block.keepalives.iter().cloned());
+        }
+        for (block, desfazer) in ramo.into_iter().zip(registros) {
+            self.gravar_desfazer(&block.hash, &desfazer).map_err(ErroBloco::Armazem)?;
+            self.total_em_circulacao += emissao_nova(block.index, block.header.extra_reward);
+            mudanca.transacoes_confirmadas.extend(block.transactions.iter().cloned());
+            self.cadeia.push(block.hash.clone());
+            mudanca.conectados.push(block);
+        }
+        self.utxos = utxos;
+        self.marcar_topo().map_err(ErroBloco::Armazem)?;
+
+        // Transações que voltaram a ser confirmadas no novo ramo não são órfãs
+        let confirmadas: Vec<&str> = mudanca.transacoes_confirmadas.iter().map(|t| t.id.as_str()).collect();
+        mudanca.transacoes_orfas.retain(|t| !confirmadas.contains(&t.id.as_str()));
+        let renovadas: HashSet<String> =
+            mudanca.conectados.iter().flat_map(|b| &b.keepalives).map(|p| p.id()).collect();
+        mudanca.provas_orfas.retain(|p| !renovadas.contains(&p.id()));
+
+        Ok(mudanca)
+    }
+
+    /// Indica se a transação passa na validação contextual (ver `Transaction::validar`)
+    pub fn verify_transaction(&self, tx: &Transaction, utxo_set: &UTXOSet) -> bool {
+        tx.validar(utxo_set).is_ok()
+    }
+
+    pub fn verify_chain(&self) -> bool {
+        let mut blocos = self.get_blocks();
+        let Some(mut anterior) = blocos.next() else {
+            return false;
+        };
+        let mut utxo = UTXOSet::from_blocos([&anterior]);
+
+        for block in blocos {
+            if self.conectar_validado(&block, &anterior, &mut utxo).is_err() {
+                println!("❌ Bloco #{} inválido", block.index);
+                return false;
+            }
+
+            anterior = block;
+        }
+
+        println!("✅ Blockchain 100% válida");
+        true
+    }
+
+    pub fn new() -> Self {
+        Blockchain::from_chain(vec![Block::genesis()])
+    }
+
+    pub fn latest_hash(&self) -> String {
+        self.cadeia.last().unwrap().clone()
+    }
+
+    pub fn height(&self) -> u64 {
+        self.cadeia.len() as u64
+    }
+
+    pub fn calcular_recompensa(altura_bloco: u64) -> u64 {
+        let recompensa_inicial = 50 * 100_000_000; // 50 VBIT
+        let halvings = altura_bloco / 210_000;
+        let recompensa = recompensa_inicial >> halvings;
+        if recompensa > 0 { recompensa } else { 0 }
+    }
+
+    /// Modelo (ainda sem prova de trabalho) do próximo bloco da cadeia ativa: a
+    /// coinbase paga a `miner_address` a emissão mais as taxas das transações
+    pub fn montar_bloco(&mut self, transacoes: Vec<Transaction>, miner_address: &str) -> Block {
+        self.montar_bloco_com_provas(transacoes, Vec::new(), miner_address)
+    }
+
+    /// Como `montar_bloco`, incluindo também as provas de vida que ainda valem.
+    /// Os UTXOs passam pelo bloco em montagem e voltam ao topo no fim.
+    pub fn montar_bloco_com_provas(
+        &mut self,
+        transacoes: Vec<Transaction>,
+        provas: Vec<ProvaDeVida>,
+        miner_address: &str,
+    ) -> Block {
+        // Hora da rede, mas sempre depois da mediana dos últimos blocos
+        let tempo_atual = self.tempo.agora().max(self.tempo_mediano() + 1);
+
+        let index = self.height();
+        let previous_hash = self.latest_hash();
+
+        // Regra dos 100 anos: os outputs reabsorvidos voltam como emissão do bloco
+        let (reabsorvidos, valores): (Vec<_>, Vec<_>) = self.utxos.reabsorviveis(index).into_iter().unzip();
+        let reabsorvido: u64 = valores.iter().map(|o| o.value).sum();
+        let mut desfazer = DesfazerBloco::default();
+        self.utxos.reabsorver(&reabsorvidos, &mut desfazer);
+        let total_reward = Blockchain::calcular_recompensa(index) + reabsorvido;
+
+        // A coinbase (de tamanho fixo, qualquer que seja o valor ou o extra-nonce) e os reabsorvidos
+        // ocupam espaço primeiro; provas e transações ficam com o que sobrar
+        let mut espaco = TAMANHO_MAX_BLOCO
+            - codificar(&Transaction::new_coinbase(miner_address, 0, index)).len()
+            - reabsorvidos.iter().map(|o| codificar(o).len()).sum::<usize>();
+
+        // Provas vencidas, que renovariam um output já renovado ou que não cabem ficam de fora
+        let mut renovados = HashSet::new();
+        let provas: Vec<ProvaDeVida> = provas
+            .into_iter()
+            .filter(|p| {
+                let tamanho = codificar(p).len();
+                let aceita = tamanho <= espaco
+                    && p.validar(&self.utxos, index).is_ok()
+                    && !p.outpoints.iter().any(|o| renovados.contains(o));
+                if aceita {
+                    espaco -= tamanho;
+                    renovados.extend(p.outpoints.iter().cloned());
+                    self.utxos.renovar(p, index, &mut desfazer);
+                }
+                aceita
+            })
+            .collect();
+
+        // As taxas das transações incluídas também vão para o minerador; as que
+        // não valem mais (gastam um output reabsorvido, por exemplo) ou não cabem ficam de fora
+        let mut txs = Vec::with_capacity(transacoes.len() + 1);
+        let mut taxas = 0;
+        for tx in transacoes {
+            let tamanho = codificar(&tx).len();
+            if tamanho > espaco {
+                continue;
+            }
+            if let Ok(taxa) = tx.validar(&self.utxos) {
+                espaco -= tamanho;
+                taxas += taxa;
+                self.utxos.conectar_transacao(&tx, false, index, &mut desfazer);
+                txs.push(tx);
+            }
+        }
+        self.utxos.desfazer_conexao(&txs, index, &desfazer);
+
+        let reward_tx = Transaction::new_coinbase(miner_address, total_reward + taxas, index);
+        txs.insert(0, reward_tx);
+
+        let mut bloco = Block::modelo(index, previous_hash, txs, total_reward, self.dificuldade());
+        bloco.header.timestamp = tempo_atual;
+        bloco.reabsorver(reabsorvidos);
+        bloco.incluir_provas_de_vida(provas);
+        bloco
+    }
+
+    pub fn is_valid(&self) -> bool {
+        let mut anterior: Option<Block> = None;
+        for current in self.get_blocks() {
+            if current.hash != current.calculate_hash() {
+                return false;
+            }
+
+            if let Some(previous) = &anterior {
+                if current.header.previous_hash != previous.hash {
+                    return false;
+                }
+            }
+            anterior = Some(current);
+        }
+        anterior.is_some()
+    }
+
+    /// Percorre a cadeia ativa do gênesis ao topo, lendo cada bloco do armazém
+    pub fn get_blocks(&self) -> impl Iterator<Item = Block> + '_ {
+        self.cadeia.iter().map(|h| {
+            self.armazem
+                .ler(h)
+                .unwrap_or_else(|e| panic!("armazém de blocos corrompido no bloco {}: {}", h, e))
+        })
+    }
+}
+
+/// Moedas que o bloco põe em circulação: o subsídio (as reabsorvidas já
+/// estavam em circulação; o gênesis não emite nada)
+fn emissao_nova(altura: u64, extra_reward: u64) -> u64 {
+    extra_reward.min(Blockchain::calcular_recompensa(altura))
+}
+
+/// Trabalho esperado para minerar um bloco com o alvo `bits` (zero se o alvo é inválido)
+fn trabalho_da_dificuldade(bits: u32) -> u128 {
+    Alvo::de_bits(bits).map(|a| a.trabalho()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::TIMESTAMP_GENESIS;
+    use crate::miner::aplicar_extra_nonce;
+    use crate::relogio::RelogioManual;
+    use crate::transaction::{OutPoint, TxInput, TxOutput, SIGHASH_ALL};
+    use crate::wallet::Wallet;
+    use secp256k1::SecretKey;
+
+    /// Cadeia em memória com o relógio parado um dia depois do gênesis
+    fn cadeia() -> (Blockchain, Arc<RelogioManual>) {
+        let mut blockchain = Blockchain::new();
+        let relogio = Arc::new(RelogioManual::new(TIMESTAMP_GENESIS + 24 * 60 * 60));
+        blockchain.usar_relogio(relogio.clone());
+        (blockchain, relogio)
+    }
+
+    /// Próximo bloco da cadeia ativa, minerado com o timestamp informado
+    fn bloco_em(blockchain: &mut Blockchain, timestamp: i64) -> Block {
+        let mut bloco = blockchain.montar_bloco(Vec::new(), "minerador");
+        bloco.header.timestamp = timestamp;
+        bloco.mine();
+        bloco
+    }
+
+    #[test]
+    fn timestamp_precisa_superar_o_tempo_mediano() {
+        let (mut blockchain, _) = cadeia();
+        for i in 1..=BLOCOS_TEMPO_MEDIANO as i64 {
+            let bloco = bloco_em(&mut blockchain, TIMESTAMP_GENESIS + i * 600);
+            blockchain.aceitar_bloco(bloco).unwrap();
+        }
+        let mediana = blockchain.tempo_mediano();
+        assert!(mediana < TIMESTAMP_GENESIS + BLOCOS_TEMPO_MEDIANO as i64 * 600);
+
+        let na_mediana = bloco_em(&mut blockchain, mediana);
+        match blockchain.aceitar_bloco(na_mediana) {
+            Err(ErroBloco::Invalido(e)) => assert!(e.contains("mediana"), "{}", e),
+            outro => panic!("bloco na mediana aceito: {:?}", outro.map(|m| m.conectados.len())),
+        }
+
+        // Basta superar a mediana, mesmo voltando no tempo em relação ao topo
+        let topo = blockchain.bloco_na_altura(blockchain.height() - 1).unwrap();
+        let antes_do_topo = bloco_em(&mut blockchain, mediana + 1);
+        assert!(antes_do_topo.header.timestamp < topo.header.timestamp);
+        assert_eq!(blockchain.aceitar_bloco(antes_do_topo).unwrap().conectados.len(), 1);
+    }
+
+    #[test]
+    fn bloco_mais_de_duas_horas_no_futuro_espera_o_relogio() {
+        let (mut blockchain, relogio) = cadeia();
+        let agora = relogio.agora();
+
+        let no_limite = bloco_em(&mut blockchain, agora + MAX_TEMPO_FUTURO);
+        assert_eq!(blockchain.aceitar_bloco(no_limite).unwrap().conectados.len(), 1);
+
+        let adiantado = bloco_em(&mut blockchain, agora + MAX_TEMPO_FUTURO + 1);
+        match blockchain.aceitar_bloco(adiantado.clone()) {
+            Err(ErroBloco::NoFuturo { timestamp, limite }) => {
+                assert_eq!(timestamp, agora + MAX_TEMPO_FUTURO + 1);
+                assert_eq!(limite, agora + MAX_TEMPO_FUTURO);
+            }
+            outro => panic!("bloco adiantado não foi adiado: {:?}", outro.map(|m| m.conectados.len())),
+        }
+
+        // Não fica marcado: o mesmo bloco entra quando o relógio alcança
+        assert!(!blockchain.conhece_bloco(&adiantado.hash));
+        relogio.avancar(1);
+        assert_eq!(blockchain.aceitar_bloco(adiantado).unwrap().conectados.len(), 1);
+    }
+
+    #[test]
+    fn modelo_cheio_continua_valido_com_extra_nonce() {
+        let (mut blockchain, _) = cadeia();
+        let carteira = Wallet::new();
+        let chave = SecretKey::from_slice(&hex::decode(&carteira.private_key).unwrap()).unwrap();
+        let gasto = TxOutput { value: 50, address: carteira.address.clone(), timestamp: 0 };
+        let outpoint = OutPoint::new(&"ef".repeat(32), 0);
+        blockchain.utxos.inserir(outpoint.clone(), gasto.clone());
+
+        // Uma transação que, com a coinbase, enche o bloco até o último byte. O DER
+        // da assinatura varia de tamanho: o enchimento se acerta na primeira
+        // tentativa e depois só o timestamp muda, até a assinatura repetir o tamanho.
+        let altura = blockchain.height();
+        let espaco = TAMANHO_MAX_BLOCO - codificar(&Transaction::new_coinbase("minerador", 0, altura)).len();
+        let mut enchimento = espaco;
+        let tx = (0..)
+            .find_map(|tentativa| {
+                let mut tx = Transaction::new(
+                    vec![TxInput { txid: outpoint.txid.clone(), index: outpoint.vout, signature: String::new(), pubkey: String::new() }],
+                    vec![TxOutput { value: 40, address: "x".repeat(enchimento), timestamp: tentativa }],
+                );
+                tx.assinar_entrada(0, std::slice::from_ref(&gasto), SIGHASH_ALL, &chave).unwrap();
+                let tamanho = codificar(&tx).len();
+                if tentativa == 0 {
+                    enchimento = enchimento + espaco - tamanho;
+                }
+                (tamanho == espaco).then_some(tx)
+            })
+            .unwrap();
+
+        let mut bloco = blockchain.montar_bloco(vec![tx], "minerador");
+        assert_eq!(bloco.transactions.len(), 2);
+        assert_eq!(bloco.tamanho(), TAMANHO_MAX_BLOCO);
+
+        aplicar_extra_nonce(&mut bloco, u64::MAX);
+        assert_eq!(bloco.tamanho(), TAMANHO_MAX_BLOCO);
+        bloco.mine();
+        assert_eq!(blockchain.aceitar_bloco(bloco).unwrap().conectados.len(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+// src/codificacao.rs
+
+use bincode::Options;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Versão do formato binário; é o primeiro byte de toda codificação
+pub const VERSAO_CODIFICACAO: u8 = 1;
+
+/// bincode com inteiros de tamanho fixo em little-endian, sem bytes sobrando.
+/// Strings e listas levam o tamanho antes (u64), campos seguem a ordem de declaração
+/// e enums começam pelo número da variante (u32).
+fn opcoes() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .reject_trailing_bytes()
+}
+
+/// Codificação canônica de blocos, transações e demais estruturas: usada para
+/// calcular hashes, trafegar na rede e gravar em disco. Os vetores de
+/// referência ficam nos testes deste módulo.
+pub fn codificar<T: Serialize + ?Sized>(valor: &T) -> Vec<u8> {
+    let mut bytes = vec![VERSAO_CODIFICACAO];
+    opcoes()
+        .serialize_into(&mut bytes, valor)
+        .expect("estruturas do VitaBit sempre têm codificação binária");
+    bytes
+}
+
+/// Lê um valor codificado por `codificar`, recusando outra versão ou bytes extras
+pub fn decodificar<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, String> {
+    match bytes.first() {
+        Some(&VERSAO_CODIFICACAO) => opcoes()
+            .deserialize(&bytes[1..])
+            .map_err(|e| format!("codificação inválida: {}", e)),
+        Some(versao) => Err(format!("versão de codificação {} não suportada", versao)),
+        None => Err("codificação vazia".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, BlockHeader};
+    use crate::transaction::{OutPoint, Transaction, TxInput, TxOutput};
+
+    // Vetores de referência escritos à mão, campo a campo: qualquer mudança
+    // na codificação canônica (e portanto nos hashes) quebra estes testes
+
+    const SAIDA: &str = concat!(
+        "01",                                 // versão
+        "0100000000000000",                   // value
+        "0100000000000000", "61",             // address "a"
+        "0200000000000000",                   // timestamp
+    );
+
+    const TRANSACAO: &str = concat!(
+        "01",                                 // versão
+        "0100000000000000", "74",             // id "t"
+        "0100000000000000",                   // 1 input
+        "0100000000000000", "70",             //   txid "p"
+        "0300000000000000",                   //   index
+        "0100000000000000", "73",             //   signature "s"
+        "0100000000000000", "6b",             //   pubkey "k"
+        "0100000000000000",                   // 1 output
+        "0500000000000000",                   //   value
+        "0100000000000000", "61",             //   address "a"
+        "ffffffffffffffff",                   //   timestamp -1
+    );
+
+    const CABECALHO: &str = concat!(
+        "01",                                 // versão
+        "01000000",                           // version (u32)
+        "0100000000000000", "70",             // previous_hash "p"
+        "0100000000000000", "6d",             // merkle_root "m"
+        "0100000000000000", "72",             // reclaimed_hash "r"
+        "0100000000000000", "6b",             // keepalive_hash "k"
+        "0700000000000000",                   // extra_reward
+        "0800000000000000",                   // timestamp
+        "ffff001d",                           // bits 0x1d00ffff
+        "0900000000000000",                   // nonce, por último
+    );
+
+    fn saida() -> TxOutput {
+        TxOutput { value: 1, address: "a".to_string(), timestamp: 2 }
+    }
+
+    fn transacao() -> Transaction {
+        Transaction {
+            id: "t".to_string(),
+            inputs: vec![TxInput {
+                txid: "p".to_string(),
+                index: 3,
+                signature: "s".to_string(),
+                pubkey: "k".to_string(),
+            }],
+            outputs: vec![TxOutput { value: 5, address: "a".to_string(), timestamp: -1 }],
+        }
+    }
+
+    fn cabecalho() -> BlockHeader {
+        BlockHeader {
+            version: 1,
+            previous_hash: "p".to_string(),
+            merkle_root: "m".to_string(),
+            reclaimed_hash: "r".to_string(),
+            keepalive_hash: "k".to_string(),
+            extra_reward: 7,
+            timestamp: 8,
+            bits: 0x1d00ffff,
+            nonce: 9,
+        }
+    }
+
+    #[test]
+    fn codifica_tx_output() {
+        assert_eq!(hex::encode(codificar(&saida())), SAIDA);
+    }
+
+    #[test]
+    fn codifica_transacao() {
+        assert_eq!(hex::encode(codificar(&transacao())), TRANSACAO);
+    }
+
+    #[test]
+    fn codifica_cabecalho() {
+        assert_eq!(hex::encode(codificar(&cabecalho())), CABECALHO);
+    }
+
+    #[test]
+    fn codifica_bloco() {
+        let bloco = Block {
+            header: cabecalho(),
+            index: 2,
+            hash: "h".to_string(),
+            transactions: vec![transacao()],
+            reclaimed: vec![OutPoint::new("o", 1)],
+            keepalives: Vec::new(),
+        };
+        // Sem o byte de versão, o cabeçalho e a transação entram como codificados sozinhos
+        let esperado = [
+            "01",
+            &CABECALHO[2..],
+            "0200000000000000",               // index
+            "0100000000000000", "68",         // hash "h"
+            "0100000000000000", &TRANSACAO[2..],
+            "0100000000000000",               // 1 reabsorvido
+            "0100000000000000", "6f",         //   txid "o"
+            "0100000000000000",               //   vout
+            "0000000000000000",               // nenhuma prova de vida
+        ]
+        .concat();
+        assert_eq!(hex::encode(codificar(&bloco)), esperado);
+    }
+
+    #[test]
+    fn decodifica_o_que_codifica() {
+        let bytes = hex::decode(TRANSACAO).unwrap();
+        let tx: Transaction = decodificar(&bytes).unwrap();
+        assert_eq!(codificar(&tx), bytes);
+    }
+
+    #[test]
+    fn recusa_outra_versao_e_bytes_sobrando() {
+        let mut bytes = hex::decode(SAIDA).unwrap();
+        bytes[0] = 2;
+        assert!(decodificar::<TxOutput>(&bytes).unwrap_err().contains("versão"));
+
+        let mut bytes = hex::decode(SAIDA).unwrap();
+        bytes.push(0);
+        assert!(decodificar::<TxOutput>(&bytes).is_err());
+        assert!(decodificar::<TxOutput>(&[]).is_err());
+    }
+}
@@ -1,19 +1,52 @@
-// src/lib.rs
-
-/// Módulo responsável pelas transações
-pub mod transaction;
-
-/// Módulo responsável por criação, criptografia e uso da carteira
-pub mod wallet;
-
-/// Módulo que define a estrutura de um bloco e mineração
-pub mod block;
-
-/// Módulo principal da cadeia de blocos (adicionar, verificar, etc)
-pub mod blockchain;
-
-/// Módulo que mantém o controle dos saldos disponíveis (UTXO)
-pub mod utxo;
-
-//rede P2P
-pub mod p2p;
\ No newline at end of file
+// src/lib.rs
+
+/// Módulo responsável pelas transações
+pub mod transaction;
+
+/// Módulo de assinatura e verificação ECDSA (traits Signer/Verifier)
+pub mod assinatura;
+
+/// Módulo responsável por criação, criptografia e uso da carteira
+pub mod wallet;
+
+/// Módulo que define a estrutura de um bloco e mineração
+pub mod block;
+
+/// Módulo do alvo de prova de trabalho (bits compactos, trabalho e reajuste)
+pub mod alvo;
+
+/// Módulo da árvore de Merkle das transações de um bloco
+pub mod merkle;
+
+/// Módulo do relógio: hora local trocável e hora ajustada pela rede
+pub mod relogio;
+
+/// Módulo principal da cadeia de blocos (adicionar, verificar, etc)
+pub mod blockchain;
+
+/// Módulo que mantém o controle dos saldos disponíveis (UTXO)
+pub mod utxo;
+
+/// Módulo da fila de transações pendentes (mempool)
+pub mod mempool;
+
+/// Módulo de mineração: modelo de bloco, busca em vários threads e taxa de hash
+pub mod miner;
+
+/// Módulo do servidor getwork para mineradores externos (modelo de bloco e submissão)
+pub mod servidor_mineracao;
+
+//rede P2P
+pub mod p2p;
+
+/// Módulo do protocolo de rede (envelope binário das mensagens)
+pub mod protocol;
+
+/// Módulo com os endereços de peers conhecidos
+pub mod peerdb;
+
+/// Módulo do armazém de blocos em disco (arquivo de dados + índice)
+pub mod armazem;
+
+/// Módulo da codificação binária canônica (hashes, rede e disco)
+pub mod codificacao;
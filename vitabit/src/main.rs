@@ -1,260 +1,342 @@
-mod block;
-mod p2p;
-mod wallet;
-mod blockchain;
-mod transaction;
-mod utxo;
-
-use crate::wallet::Wallet;
-use crate::blockchain::Blockchain;
-use crate::utxo::UTXOSet;
-use crate::transaction::{TxInput, TxOutput, Transaction};
-use crate::block::Block;
-
-use std::net::TcpStream;
-use rpassword::prompt_password;
-use std::io::{self, Write};
-use sha2::Digest;
-use hex;
-use chrono::Utc;
-use serde_json;
-use p2p::P2PServer;
-use std::sync::{Arc, Mutex};
-use std::thread;
-
-fn main() {
-    let dev_mode = true; // mude para true se quiser ver a chave privada/ false para esconder
-    println!("=== VitaBit CLI ===");
-    
-
-    let nome = "usuario";
-    let senha = prompt_password("\u{1f510} Digite a senha da carteira: ").unwrap();
-
-    let wallet = if let Some(w) = Wallet::load_encrypted(nome, &senha) {
-        println!("\u{2705} Carteira carregada.");
-        w
-    } else {
-        println!("⚠️ Nenhuma carteira encontrada. Criando nova...");
-        let w = Wallet::new();
-        w.save_encrypted(nome, &senha).unwrap();
-        w
-    };
-
-    wallet.show(dev_mode);
-    println!("👷 Recompensa do bloco gênesis atribuída a: {}", wallet.address);
-
-    let caminho_bc = "blockchain.json";
-    let mut bc = if let Some(bc) = Blockchain::carregar_de_arquivo(caminho_bc) {
-        println!("\u{2705} Blockchain carregada do disco.");
-        bc
-    } else {
-        println!("⚠️ Nenhuma blockchain encontrada. Criando nova...");
-        Blockchain::new()
-    };
-
-    bc.salvar_em_arquivo(caminho_bc).expect("Erro ao salvar blockchain.");
-    let mut utxos = UTXOSet::from_blockchain(&bc);
-
-    let genesis_reward = Blockchain::calcular_recompensa(0);
-    let coinbase_tx = Transaction::new_coinbase(&wallet.address, genesis_reward);
-    let bloco_genesis = bc.add_block(
-        serde_json::to_string(&vec![coinbase_tx.clone()]).unwrap(),
-        &wallet.address,
-        &mut utxos,
-    );
-
-    let bloco_json = match serde_json::to_string(&bloco_genesis) {
-        Ok(json) => json,
-        Err(e) => {
-            eprintln!("\u{274c} Erro ao serializar bloco gênesis: {}", e);
-            return;
-        }
-    };
-
-    let blockchain_arc = Arc::new(Mutex::new(bc.clone()));
-    let servidor_p2p = P2PServer::new();
-
-    let servidor_clone = servidor_p2p.clone();
-    let blockchain_clone = Arc::clone(&blockchain_arc);
-
-    thread::spawn(move || {
-        servidor_clone.start(6010, blockchain_clone);
-    });
-
-    servidor_p2p.iter_peers(|peer| {
-        if let Ok(mut stream) = TcpStream::connect(peer) {
-            servidor_p2p.enviar_bloco(&mut stream, &bloco_json);
-            println!("\u{1f4e4} Bloco gênesis enviado para peer {}", peer);
-        } else {
-            eprintln!("⚠️ Falha ao conectar ao peer {}", peer);
-        }
-    });
-
-    println!("\u{2705} Bloco gênesis criado: {}", bloco_genesis.index);
-    println!("\u{1f4b0} Saldo atual: {} VBIT", utxos.balance(&wallet.address));
-    println!("\u{2705} Blockchain válida? {}", bc.is_valid());
-
-    loop {
-        println!("\nEscolha uma opção:");
-        println!("1. Consultar saldo");
-        println!("2. Enviar transação");
-        println!("3. Verificar blocos");
-        println!("4. Listar peers conectados");
-        println!("5. Conectar a um peer remoto");
-        println!("7. Exportar chave privada (backup)");
-        println!("8. Restaurar carteira de backup");
-        println!("6. Sair");
-
-        let mut escolha = String::new();
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut escolha).unwrap();
-
-        match escolha.trim() {
-            "1" => consultar_saldo(&utxos, &wallet),
-            "2" => enviar_transacao(&mut blockchain_arc.lock().unwrap(), &mut utxos, &wallet, &servidor_p2p),
-            "3" => verificar_blocos(&blockchain_arc.lock().unwrap()),
-            "4" => servidor_p2p.listar_peers(),
-            "5" => {
-                let mut endereco = String::new();
-                println!("Digite o endereço do peer (ex: 127.0.0.1:6010):");
-                io::stdin().read_line(&mut endereco).unwrap();
-                servidor_p2p.conectar_a_peer(endereco.trim());
-            }
-            
-            "7" => {
-                
-    println!("Digite o caminho do arquivo para salvar (ex: backup.key):");
-    let mut caminho = String::new();
-    io::stdin().read_line(&mut caminho).unwrap();
-
-    let caminho = caminho.trim();
-    match wallet.export_private_key_encrypted(&senha, caminho) {
-        Ok(_) => println!("🔐 Backup da chave privada exportado para '{}'", caminho),
-        Err(e) => eprintln!("❌ Falha ao exportar chave privada: {}", e),
-        
-    }
-    
-            }
-
-            "8" => {
-    println!("Digite o caminho do backup (ex: backup.key):");
-    let mut caminho = String::new();
-    io::stdin().read_line(&mut caminho).unwrap();
-    let caminho = caminho.trim();
-
-    match Wallet::import_private_key_encrypted(&senha, caminho) {
-        Ok(w) => {
-            println!("✅ Carteira restaurada com sucesso!");
-            w.show(false);
-        }
-        Err(e) => {
-            eprintln!("❌ Falha ao restaurar carteira: {}", e);
-        }
-    }
-}
-
-
-            "6" => {
-                println!("Saindo...");
-                break;
-            }
-            _ => println!("Opção inválida!"),
-        }
-    }
-}
-
-fn consultar_saldo(utxos: &UTXOSet, wallet: &Wallet) {
-    let saldo = utxos.balance(&wallet.address);
-    println!("\u{1f4b0} Saldo atual: {} VBIT", saldo);
-}
-
-fn enviar_transacao(bc: &mut Blockchain, utxos: &mut UTXOSet, wallet: &Wallet, servidor_p2p: &P2PServer) {
-    let mut destino = String::new();
-    let mut valor_str = String::new();
-
-    println!("Digite o endereço de destino:");
-    std::io::stdin().read_line(&mut destino).unwrap();
-    let destino = destino.trim();
-
-    println!("Digite o valor a enviar (em VBIT):");
-    std::io::stdin().read_line(&mut valor_str).unwrap();
-    let valor: u64 = match valor_str.trim().parse() {
-        Ok(v) => v,
-        Err(_) => {
-            println!("⚠️ Valor inválido.");
-            return;
-        }
-    };
-
-    let meus_utxos = utxos.find_by_address(&wallet.address);
-    let mut acumulado = 0;
-    let mut inputs = vec![];
-
-    for (txid, index, output) in meus_utxos {
-        acumulado += output.value;
-        let msg = format!("{}{}", txid, index);
-        let msg_hash = sha2::Sha256::digest(msg.as_bytes());
-        let assinatura = wallet.assinar(&msg_hash);
-        let assinatura_hex = hex::encode(assinatura);
-
-        inputs.push(TxInput {
-            txid,
-            index,
-            signature: assinatura_hex,
-            pubkey: wallet.public_key.clone(),
-        });
-
-        if acumulado >= valor {
-            break;
-        }
-    }
-
-    if acumulado < valor {
-        println!("⚠️ Saldo insuficiente.");
-        return;
-    }
-
-    let mut outputs = vec![TxOutput {
-        value: valor,
-        address: destino.to_string(),
-        timestamp: Utc::now().timestamp(),
-    }];
-
-    if acumulado > valor {
-        outputs.push(TxOutput {
-            value: acumulado - valor,
-            address: wallet.address.clone(),
-            timestamp: Utc::now().timestamp(),
-        });
-    }
-
-    let tx = Transaction::new(inputs, outputs);
-    let txs = vec![tx.clone()];
-    let txs_json = serde_json::to_string(&txs).unwrap();
-
-    let novo_bloco = bc.add_block(txs_json, &wallet.address, utxos);
-    *utxos = UTXOSet::from_blockchain(bc);
-
-    println!("\u{2705} Transação incluída no bloco {}", novo_bloco.index);
-
-    for peer in servidor_p2p.get_peers().iter() {
-        servidor_p2p.enviar_transacao(peer, &tx);
-    }
-
-    let bloco_json = serde_json::to_string(&novo_bloco).unwrap();
-    for peer in servidor_p2p.get_peers().iter() {
-        if let Ok(mut stream) = TcpStream::connect(peer) {
-            servidor_p2p.enviar_bloco(&mut stream, &bloco_json);
-        }
-    }
-}
-
-fn verificar_blocos(bc: &Blockchain) {
-    let blocos = bc.get_blocks();
-    println!("\u{1f4e6} Blocos na blockchain:");
-    for bloco in blocos {
-        println!("\n\u{1f539} Bloco #{}", bloco.index);
-        println!("   Data: {}", bloco.timestamp);
-        println!("   Transações: {}", bloco.transactions.len());
-    }
-}
+use vitabit::wallet::{Wallet, AVISO_REABSORCAO};
+use vitabit::blockchain::{Blockchain, TEMPO_POR_BLOCO};
+use vitabit::utxo::{UTXOSet, MATURIDADE_COINBASE};
+use vitabit::mempool::Mempool;
+use vitabit::transaction::JANELA_RENOVACAO;
+use vitabit::miner::{Mineracao, Minerador};
+use vitabit::p2p::{P2PServer, PORTA_PADRAO};
+use vitabit::servidor_mineracao::{ServidorMineracao, PORTA_MINERACAO};
+
+use rpassword::prompt_password;
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+fn main() {
+    let dev_mode = true; // mude para true se quiser ver a chave privada/ false para esconder
+    println!("=== VitaBit CLI ===");
+    
+
+    let nome = "usuario";
+    let senha = prompt_password("\u{1f510} Digite a senha da carteira: ").unwrap();
+
+    let wallet = if let Some(w) = Wallet::load_encrypted(nome, &senha) {
+        println!("\u{2705} Carteira carregada.");
+        w
+    } else {
+        println!("⚠️ Nenhuma carteira encontrada. Criando nova...");
+        let w = Wallet::new();
+        w.save_encrypted(nome, &senha).unwrap();
+        w
+    };
+
+    wallet.show(dev_mode);
+    println!("👷 Recompensa dos blocos minerados atribuída a: {}", wallet.address);
+
+    // Blocos ficam no armazém em disco (blocos.dat + blocos.idx); cada bloco é gravado ao entrar na cadeia
+    let bc = Blockchain::abrir(Path::new(".")).expect("Erro ao abrir blockchain");
+    println!("\u{2705} Blockchain aberta com {} bloco(s).", bc.height());
+
+    let blockchain_arc = Arc::new(Mutex::new(bc));
+    let mempool_arc = Arc::new(Mutex::new(Mempool::new()));
+
+    // VITABIT_PORTA escolhe a porta local; VITABIT_SEEDS lista nós iniciais separados por vírgula
+    let porta = env::var("VITABIT_PORTA")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(PORTA_PADRAO);
+    let servidor_p2p = P2PServer::new(porta, Arc::clone(&blockchain_arc), Arc::clone(&mempool_arc));
+    if let Ok(seeds) = env::var("VITABIT_SEEDS") {
+        let seeds: Vec<String> = seeds
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        servidor_p2p.adicionar_seeds(&seeds);
+    }
+
+    // A rede roda nas threads do runtime; o menu continua síncrono
+    let runtime = tokio::runtime::Runtime::new().expect("Erro ao iniciar runtime do tokio");
+    runtime.block_on(servidor_p2p.start()).expect("Erro ao iniciar servidor P2P");
+
+    {
+        let bc = blockchain_arc.lock().unwrap();
+        consultar_saldo(bc.utxos(), &wallet);
+        println!("\u{2705} Blockchain válida? {}", bc.is_valid());
+        avisar_reabsorcao(bc.utxos(), &wallet);
+    }
+
+    // O bloco de recompensa da carteira é minerado em segundo plano, como na opção 9
+    let minerador = Minerador::com_servidor(&servidor_p2p);
+    let mut mineracao: Option<Mineracao> = Some(minerador.iniciar(&wallet.address));
+    println!("⛏️ Minerando o bloco de recompensa da carteira em {} thread(s)", minerador.threads());
+
+    // VITABIT_PORTA_MINERACAO escolhe a porta local (127.0.0.1) para mineradores externos
+    let porta_mineracao = env::var("VITABIT_PORTA_MINERACAO")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(PORTA_MINERACAO);
+    let servidor_mineracao = ServidorMineracao::new(minerador.clone(), porta_mineracao, &wallet.address);
+    if let Err(e) = runtime.block_on(servidor_mineracao.start()) {
+        println!("⚠️ {}", e);
+    }
+
+    loop {
+        println!("\nEscolha uma opção:");
+        println!("1. Consultar saldo");
+        println!("2. Enviar transação");
+        println!("3. Verificar blocos");
+        println!("4. Listar peers conectados");
+        println!("5. Conectar a um peer remoto");
+        println!("7. Exportar chave privada (backup)");
+        println!("8. Restaurar carteira de backup");
+        println!("9. Minerar bloco com transações pendentes (em segundo plano)");
+        println!("10. Sincronizar blockchain com os peers");
+        println!("11. Listar peers banidos");
+        println!("12. Parar mineração");
+        println!("13. Relatório da regra dos 100 anos");
+        println!("14. Enviar prova de vida (renova os outputs sem gastá-los)");
+        println!("6. Sair");
+
+        let mut escolha = String::new();
+        io::stdout().flush().unwrap();
+        io::stdin().read_line(&mut escolha).unwrap();
+
+        // Os UTXOs são lidos da própria blockchain, sob o lock, só quando a opção precisa deles
+        match escolha.trim() {
+            "1" => consultar_saldo(blockchain_arc.lock().unwrap().utxos(), &wallet),
+            "2" => enviar_transacao(&blockchain_arc, &mempool_arc, &wallet, &servidor_p2p),
+            "3" => verificar_blocos(&blockchain_arc.lock().unwrap()),
+            "4" => servidor_p2p.listar_peers(),
+            "5" => {
+                let mut endereco = String::new();
+                println!("Digite o endereço do peer (ex: 127.0.0.1:6010):");
+                io::stdin().read_line(&mut endereco).unwrap();
+                let endereco = endereco.trim();
+                // a sincronização começa sozinha se o peer tiver uma cadeia maior
+                if let Err(e) = runtime.block_on(servidor_p2p.conectar_a_peer(endereco)) {
+                    println!("❌ Falha ao conectar ao peer {}: {}", endereco, e);
+                }
+            }
+            
+            "7" => {
+                
+    println!("Digite o caminho do arquivo para salvar (ex: backup.key):");
+    let mut caminho = String::new();
+    io::stdin().read_line(&mut caminho).unwrap();
+
+    let caminho = caminho.trim();
+    match wallet.export_private_key_encrypted(&senha, caminho) {
+        Ok(_) => println!("🔐 Backup da chave privada exportado para '{}'", caminho),
+        Err(e) => eprintln!("❌ Falha ao exportar chave privada: {}", e),
+        
+    }
+    
+            }
+
+            "8" => {
+    println!("Digite o caminho do backup (ex: backup.key):");
+    let mut caminho = String::new();
+    io::stdin().read_line(&mut caminho).unwrap();
+    let caminho = caminho.trim();
+
+    match Wallet::import_private_key_encrypted(&senha, caminho) {
+        Ok(w) => {
+            println!("✅ Carteira restaurada com sucesso!");
+            w.show(false);
+        }
+        Err(e) => {
+            eprintln!("❌ Falha ao restaurar carteira: {}", e);
+        }
+    }
+}
+
+
+            // a busca roda em outros threads; o menu e a rede seguem livres
+            "9" => match &mineracao {
+                Some(m) if !m.terminou() => println!("⛏️ Mineração em andamento ({:.0} H/s)", m.taxa_de_hash()),
+                _ => {
+                    mineracao = Some(minerador.iniciar(&wallet.address));
+                    println!("⛏️ Mineração iniciada em {} thread(s)", minerador.threads());
+                }
+            },
+            "12" => match mineracao.take() {
+                Some(m) if !m.terminou() => {
+                    m.cancelar();
+                    println!("🛑 Mineração cancelada após {} hashes", m.hashes());
+                }
+                _ => println!("Nenhuma mineração em andamento."),
+            },
+
+            "10" => runtime.block_on(servidor_p2p.sincronizar_com_peers()),
+            "11" => servidor_p2p.listar_banidos(),
+            "13" => relatorio_dormencia(blockchain_arc.lock().unwrap().utxos(), &wallet),
+            "14" => enviar_prova_de_vida(&blockchain_arc, &mempool_arc, &wallet, &servidor_p2p),
+
+            "6" => {
+                println!("Saindo...");
+                if let Some(m) = mineracao.take() {
+                    m.cancelar();
+                    m.aguardar();
+                }
+                servidor_mineracao.encerrar();
+                runtime.block_on(servidor_p2p.encerrar());
+                if let Err(e) = blockchain_arc.lock().unwrap().salvar_utxos() {
+                    eprintln!("⚠️ {}", e);
+                }
+                break;
+            }
+            _ => println!("Opção inválida!"),
+        }
+    }
+}
+
+fn consultar_saldo(utxos: &UTXOSet, wallet: &Wallet) {
+    let saldo = utxos.balance(&wallet.address);
+    println!("\u{1f4b0} Saldo atual: {} VBIT", saldo);
+}
+
+/// Lê destino, valor e taxa antes de tocar em qualquer lock: a rede segue
+/// livre enquanto o usuário digita
+fn enviar_transacao(blockchain: &Mutex<Blockchain>, mempool: &Mutex<Mempool>, wallet: &Wallet, servidor_p2p: &P2PServer) {
+    let mut destino = String::new();
+    let mut valor_str = String::new();
+    let mut taxa_str = String::new();
+
+    println!("Digite o endereço de destino:");
+    std::io::stdin().read_line(&mut destino).unwrap();
+    let destino = destino.trim();
+
+    println!("Digite o valor a enviar (em VBIT):");
+    std::io::stdin().read_line(&mut valor_str).unwrap();
+    let valor: u64 = match valor_str.trim().parse() {
+        Ok(v) => v,
+        Err(_) => {
+            println!("⚠️ Valor inválido.");
+            return;
+        }
+    };
+
+    println!("Digite a taxa (em VBIT, Enter para 0):");
+    std::io::stdin().read_line(&mut taxa_str).unwrap();
+    let taxa: u64 = match taxa_str.trim() {
+        "" => 0,
+        t => match t.parse() {
+            Ok(v) => v,
+            Err(_) => {
+                println!("⚠️ Taxa inválida.");
+                return;
+            }
+        },
+    };
+
+    // Mesma ordem de locks da rede: blockchain antes da mempool
+    let bc = blockchain.lock().unwrap();
+    let mut mempool = mempool.lock().unwrap();
+    let tx = match wallet.create_transaction(destino, valor, taxa, bc.utxos(), &mempool) {
+        Some(tx) => tx,
+        None => {
+            println!(
+                "⚠️ Saldo disponível insuficiente (recompensas de mineração só podem ser gastas após {} blocos).",
+                MATURIDADE_COINBASE
+            );
+            return;
+        }
+    };
+
+    match mempool.adicionar(tx.clone(), bc.utxos()) {
+        Ok(_) => println!("\u{2705} Transação {} adicionada à mempool ({} pendentes)", tx.id, mempool.len()),
+        Err(e) => {
+            println!("❌ Transação rejeitada: {}", e);
+            return;
+        }
+    }
+    drop(mempool);
+    drop(bc);
+
+    servidor_p2p.anunciar_transacao(&tx);
+}
+
+/// Avisa dos outputs da carteira que serão reabsorvidos em menos de AVISO_REABSORCAO
+fn avisar_reabsorcao(utxos: &UTXOSet, wallet: &Wallet) {
+    let urgentes: Vec<_> = wallet
+        .relatorio_dormencia(utxos)
+        .into_iter()
+        .filter(|d| d.segundos_restantes < AVISO_REABSORCAO)
+        .collect();
+    for d in &urgentes {
+        println!(
+            "⚠️ {} VBIT em {} serão reabsorvidos em {} ({} blocos)",
+            d.valor, d.outpoint, formatar_prazo(d.segundos_restantes), d.blocos_restantes
+        );
+    }
+    if !urgentes.is_empty() {
+        println!("⚠️ Envie uma prova de vida (opção 14) para renovar esses outputs.");
+    }
+}
+
+fn relatorio_dormencia(utxos: &UTXOSet, wallet: &Wallet) {
+    let relatorio = wallet.relatorio_dormencia(utxos);
+    if relatorio.is_empty() {
+        println!("Nenhum output na carteira.");
+        return;
+    }
+    println!("\u{23f3} Tempo até a reabsorção pela regra dos 100 anos:");
+    for d in &relatorio {
+        let aviso = if d.segundos_restantes < AVISO_REABSORCAO { " ⚠️" } else { "" };
+        println!(
+            "   {}  {} VBIT  {} ({} blocos){}",
+            d.outpoint, d.valor, formatar_prazo(d.segundos_restantes), d.blocos_restantes, aviso
+        );
+    }
+}
+
+fn enviar_prova_de_vida(blockchain: &Mutex<Blockchain>, mempool: &Mutex<Mempool>, wallet: &Wallet, servidor_p2p: &P2PServer) {
+    let bc = blockchain.lock().unwrap();
+    let mut mempool = mempool.lock().unwrap();
+    let prova = match wallet.criar_prova_de_vida(bc.utxos()) {
+        Some(p) => p,
+        None => {
+            println!(
+                "Nenhum output da carteira para renovar: só os que serão reabsorvidos em menos de {} aceitam prova de vida.",
+                formatar_prazo(JANELA_RENOVACAO as i64 * TEMPO_POR_BLOCO)
+            );
+            return;
+        }
+    };
+
+    match mempool.adicionar_prova(prova.clone(), bc.utxos()) {
+        Ok(_) => println!("\u{2705} Prova de vida {} renovando {} output(s) adicionada à mempool", prova.id(), prova.outpoints.len()),
+        Err(e) => {
+            println!("❌ Prova de vida rejeitada: {}", e);
+            return;
+        }
+    }
+    drop(mempool);
+    drop(bc);
+
+    servidor_p2p.anunciar_prova_de_vida(&prova);
+}
+
+/// Prazo legível, em anos e dias
+fn formatar_prazo(segundos: i64) -> String {
+    const DIA: i64 = 24 * 60 * 60;
+    let dias = segundos / DIA;
+    match (dias / 365, dias % 365) {
+        (0, 0) => format!("{} horas", segundos / 3600),
+        (0, d) => format!("{} dias", d),
+        (a, d) => format!("{} anos e {} dias", a, d),
+    }
+}
+
+fn verificar_blocos(bc: &Blockchain) {
+    let blocos = bc.get_blocks();
+    println!("\u{1f4e6} Blocos na blockchain:");
+    for bloco in blocos {
+        println!("\n\u{1f539} Bloco #{}", bloco.index);
+        println!("   Data: {}", bloco.header.timestamp);
+        println!("   Merkle: {}", bloco.header.merkle_root);
+        println!("   Transações: {}", bloco.transactions.len());
+    }
+}
@@ -0,0 +1,340 @@
+// src/mempool.rs
+
+use crate::transaction::{OutPoint, ProvaDeVida, Transaction};
+use crate::utxo::{UTXOSet, BLOCOS_100_ANOS};
+use crate::codificacao::codificar;
+
+use chrono::Utc;
+use std::collections::HashMap;
+
+/// Tamanho máximo (em bytes serializados) ocupado pela fila de pendentes
+pub const MEMPOOL_MAX_BYTES: usize = 5_000_000;
+
+/// Máximo de provas de vida pendentes. Elas não pagam taxa: com a fila cheia,
+/// sai a de prazo mais folgado para entrar uma mais urgente
+pub const MAX_PROVAS_PENDENTES: usize = 1000;
+
+/// Transação pendente junto com os dados usados para ordenação
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    pub tx: Transaction,
+    pub taxa: u64,       // soma(inputs) - soma(outputs)
+    pub tamanho: usize,  // bytes da transação serializada
+    pub recebida_em: i64,
+}
+
+impl MempoolEntry {
+    /// Taxa por byte, usada para priorizar a inclusão em blocos
+    fn taxa_por_byte(&self) -> f64 {
+        self.taxa as f64 / self.tamanho.max(1) as f64
+    }
+}
+
+/// Fila de transações válidas aguardando inclusão em um bloco
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    entradas: HashMap<String, MempoolEntry>,   // txid → entrada
+    gastos: HashMap<OutPoint, String>,         // outpoint gasto → txid que o gasta
+    bytes_totais: usize,
+    max_bytes: usize,
+    provas: HashMap<String, ProvaPendente>,    // id → prova de vida pendente
+}
+
+/// Prova de vida pendente com o prazo do seu output mais perto da reabsorção
+#[derive(Debug, Clone)]
+struct ProvaPendente {
+    prova: ProvaDeVida,
+    prazo: u64, // altura em que o output mais urgente da prova seria reabsorvido
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mempool {
+    /// Cria uma mempool vazia com o limite padrão
+    pub fn new() -> Self {
+        Self::com_limite(MEMPOOL_MAX_BYTES)
+    }
+
+    /// Cria uma mempool vazia com limite de tamanho personalizado
+    pub fn com_limite(max_bytes: usize) -> Self {
+        Mempool {
+            entradas: HashMap::new(),
+            gastos: HashMap::new(),
+            bytes_totais: 0,
+            max_bytes,
+            provas: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entradas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entradas.is_empty()
+    }
+
+    pub fn contem(&self, txid: &str) -> bool {
+        self.entradas.contains_key(txid)
+    }
+
+    /// Indica se algum pendente já gasta o outpoint informado
+    pub fn esta_gasto(&self, outpoint: &OutPoint) -> bool {
+        self.gastos.contains_key(outpoint)
+    }
+
+    /// Valida a transação contra o UTXOSet e a adiciona à fila.
+    /// Retorna a taxa paga ou o motivo da rejeição.
+    pub fn adicionar(&mut self, tx: Transaction, utxo_set: &UTXOSet) -> Result<u64, String> {
+        if self.contem(&tx.id) {
+            return Err("transação já está na mempool".to_string());
+        }
+
+        let taxa = tx.validar(utxo_set).map_err(|e| e.to_string())?;
+
+        for input in &tx.inputs {
+            if let Some(outro) = self.gastos.get(&input.outpoint()) {
+                return Err(format!("gasto duplo de {} (já gasto por {})", input.outpoint(), outro));
+            }
+        }
+
+        let tamanho = codificar(&tx).len();
+
+        let txid = tx.id.clone();
+        self.inserir(MempoolEntry {
+            tx,
+            taxa,
+            tamanho,
+            recebida_em: Utc::now().timestamp(),
+        });
+        self.aplicar_limite();
+
+        if !self.contem(&txid) {
+            return Err("taxa insuficiente: mempool cheia".to_string());
+        }
+
+        Ok(taxa)
+    }
+
+    /// Remove e retorna as transações de maior taxa por byte que cabem em `max_bytes`
+    pub fn drenar_para_bloco(&mut self, max_bytes: usize) -> Vec<Transaction> {
+        let selecionadas = self.selecionar_para_bloco(max_bytes);
+        for tx in &selecionadas {
+            self.remover(&tx.id);
+        }
+        selecionadas
+    }
+
+    /// As transações de maior taxa por byte que cabem em `max_bytes`, sem tirá-las
+    /// da fila: elas só saem quando o bloco minerado for conectado
+    pub fn selecionar_para_bloco(&self, max_bytes: usize) -> Vec<Transaction> {
+        let mut selecionadas = Vec::new();
+        let mut usados = 0;
+
+        for txid in self.ordenadas_por_taxa() {
+            let entrada = &self.entradas[&txid];
+            if usados + entrada.tamanho > max_bytes {
+                continue;
+            }
+            usados += entrada.tamanho;
+            selecionadas.push(entrada.tx.clone());
+        }
+
+        selecionadas
+    }
+
+    /// Retira as transações confirmadas em um bloco e as que conflitam com elas
+    pub fn remover_confirmadas(&mut self, txs: &[Transaction]) {
+        for tx in txs {
+            self.remover(&tx.id);
+            for input in &tx.inputs {
+                if let Some(conflito) = self.gastos.get(&input.outpoint()).cloned() {
+                    self.remover(&conflito);
+                }
+            }
+        }
+    }
+
+    /// Descarta as transações que deixaram de valer contra `utxo_set`: as que
+    /// gastam outputs reabsorvidos ou criados em blocos desconectados
+    pub fn revalidar_transacoes(&mut self, utxo_set: &UTXOSet) {
+        let invalidas: Vec<String> = self
+            .entradas
+            .values()
+            .filter(|e| e.tx.validar(utxo_set).is_err())
+            .map(|e| e.tx.id.clone())
+            .collect();
+        for txid in invalidas {
+            self.remover(&txid);
+        }
+    }
+
+    /// Valida a prova de vida para o próximo bloco e a deixa pendente; recusa
+    /// prova que renova output já coberto por outra pendente. Com a fila cheia,
+    /// a prova só entra se for mais urgente que a de prazo mais folgado, que sai.
+    pub fn adicionar_prova(&mut self, prova: ProvaDeVida, utxo_set: &UTXOSet) -> Result<(), String> {
+        let id = prova.id();
+        if self.provas.contains_key(&id) {
+            return Err("prova de vida já está na mempool".to_string());
+        }
+        prova.validar(utxo_set, utxo_set.proxima_altura())?;
+        if let Some(outra) = self
+            .provas
+            .values()
+            .find(|p| p.prova.outpoints.iter().any(|o| prova.outpoints.binary_search(o).is_ok()))
+        {
+            return Err(format!("output já coberto pela prova de vida {}", outra.prova.id()));
+        }
+
+        // Validada, a prova só tem outputs com origem conhecida
+        let prazo = prova
+            .outpoints
+            .iter()
+            .filter_map(|o| utxo_set.origem(o))
+            .map(|o| o.vivo_desde + BLOCOS_100_ANOS)
+            .min()
+            .unwrap_or(0);
+        if self.provas.len() >= MAX_PROVAS_PENDENTES {
+            let (folgada, prazo_folgado) = self
+                .provas
+                .iter()
+                .map(|(id, p)| (id.clone(), p.prazo))
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)))
+                .expect("fila cheia não está vazia");
+            if prazo >= prazo_folgado {
+                return Err("mempool cheia de provas de vida mais urgentes".to_string());
+            }
+            self.provas.remove(&folgada);
+        }
+
+        self.provas.insert(id, ProvaPendente { prova, prazo });
+        Ok(())
+    }
+
+    pub fn contem_prova(&self, id: &str) -> bool {
+        self.provas.contains_key(id)
+    }
+
+    /// Provas de vida pendentes que cabem em `max_bytes`, as mais antigas primeiro
+    /// (estão mais perto de vencer)
+    pub fn provas_para_bloco(&self, max_bytes: usize) -> Vec<ProvaDeVida> {
+        let mut provas: Vec<&ProvaDeVida> = self.provas.values().map(|p| &p.prova).collect();
+        provas.sort_by_key(|p| (p.altura, p.id()));
+
+        let mut usados = 0;
+        let mut selecionadas = Vec::new();
+        for prova in provas {
+            let tamanho = codificar(prova).len();
+            if usados + tamanho > max_bytes {
+                continue;
+            }
+            usados += tamanho;
+            selecionadas.push(prova.clone());
+        }
+        selecionadas
+    }
+
+    /// Descarta as provas de vida que não valem mais para o próximo bloco:
+    /// confirmadas, vencidas ou com outputs gastos ou reabsorvidos
+    pub fn revalidar_provas(&mut self, utxo_set: &UTXOSet) {
+        let altura = utxo_set.proxima_altura();
+        self.provas.retain(|_, p| p.prova.validar(utxo_set, altura).is_ok());
+    }
+
+    pub fn len_provas(&self) -> usize {
+        self.provas.len()
+    }
+
+    /// Lista as transações pendentes, da maior para a menor taxa por byte
+    pub fn transacoes(&self) -> Vec<&MempoolEntry> {
+        self.ordenadas_por_taxa()
+            .iter()
+            .map(|txid| &self.entradas[txid])
+            .collect()
+    }
+
+    fn inserir(&mut self, entrada: MempoolEntry) {
+        for input in &entrada.tx.inputs {
+            self.gastos.insert(input.outpoint(), entrada.tx.id.clone());
+        }
+        self.bytes_totais += entrada.tamanho;
+        self.entradas.insert(entrada.tx.id.clone(), entrada);
+    }
+
+    fn remover(&mut self, txid: &str) -> Option<MempoolEntry> {
+        let entrada = self.entradas.remove(txid)?;
+        for input in &entrada.tx.inputs {
+            self.gastos.remove(&input.outpoint());
+        }
+        self.bytes_totais -= entrada.tamanho;
+        Some(entrada)
+    }
+
+    /// Despeja as transações de menor taxa por byte até caber no limite
+    fn aplicar_limite(&mut self) {
+        while self.bytes_totais > self.max_bytes {
+            let pior = match self.ordenadas_por_taxa().pop() {
+                Some(txid) => txid,
+                None => break,
+            };
+            self.remover(&pior);
+        }
+    }
+
+    /// Txids ordenados por taxa por byte decrescente (desempate: mais antiga primeiro)
+    fn ordenadas_por_taxa(&self) -> Vec<String> {
+        let mut ordem: Vec<&MempoolEntry> = self.entradas.values().collect();
+        ordem.sort_by(|a, b| {
+            b.taxa_por_byte()
+                .total_cmp(&a.taxa_por_byte())
+                .then(a.recebida_em.cmp(&b.recebida_em))
+                .then(a.tx.id.cmp(&b.tx.id))
+        });
+        ordem.into_iter().map(|e| e.tx.id.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{TxInput, TxOutput, SIGHASH_ALL};
+    use crate::wallet::Wallet;
+    use secp256k1::SecretKey;
+
+    fn gasto_assinado(carteira: &Wallet, outpoint: &OutPoint, gasto: &TxOutput) -> Transaction {
+        let chave = SecretKey::from_slice(&hex::decode(&carteira.private_key).unwrap()).unwrap();
+        let input = TxInput { txid: outpoint.txid.clone(), index: outpoint.vout, signature: String::new(), pubkey: String::new() };
+        let mut tx = Transaction::new(vec![input], vec![TxOutput { value: gasto.value - 1, address: "destino".to_string(), timestamp: 0 }]);
+        tx.assinar_entrada(0, std::slice::from_ref(gasto), SIGHASH_ALL, &chave).unwrap();
+        tx
+    }
+
+    #[test]
+    fn revalidar_descarta_transacoes_sem_os_outputs_gastos() {
+        let carteira = Wallet::new();
+        let mut utxos = UTXOSet::new();
+        let mut mempool = Mempool::new();
+        let mut txids = Vec::new();
+        for vout in 0..2 {
+            let outpoint = OutPoint::new(&"ab".repeat(32), vout);
+            let gasto = TxOutput { value: 50, address: carteira.address.clone(), timestamp: 0 };
+            utxos.inserir(outpoint.clone(), gasto.clone());
+            let tx = gasto_assinado(&carteira, &outpoint, &gasto);
+            txids.push(tx.id.clone());
+            mempool.adicionar(tx, &utxos).unwrap();
+        }
+
+        // O primeiro output some do conjunto, como numa reabsorção ou reorganização
+        utxos.remove(&OutPoint::new(&"ab".repeat(32), 0));
+        mempool.revalidar_transacoes(&utxos);
+
+        assert!(!mempool.contem(&txids[0]));
+        assert!(!mempool.esta_gasto(&OutPoint::new(&"ab".repeat(32), 0)));
+        assert!(mempool.contem(&txids[1]));
+        assert_eq!(mempool.len(), 1);
+    }
+}
@@ -0,0 +1,173 @@
+// src/merkle.rs
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+
+type No = [u8; 32];
+
+/// Raiz de uma árvore sem folhas (bloco sem transações)
+pub const RAIZ_VAZIA: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Caminho de uma transação até a raiz de Merkle: com ele, quem só tem o
+/// cabeçalho do bloco confere que a transação está lá dentro
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvaMerkle {
+    pub txid: String,
+    pub posicao: usize,      // posição da transação no bloco
+    pub irmaos: Vec<String>, // hash do nó vizinho em cada nível, das folhas para a raiz
+}
+
+impl ProvaMerkle {
+    /// Monta a prova da transação em `posicao`
+    pub fn gerar(txids: &[String], posicao: usize) -> Option<ProvaMerkle> {
+        let mut nivel: Vec<No> = txids.iter().map(|t| folha(t)).collect();
+        if posicao >= nivel.len() {
+            return None;
+        }
+
+        let mut irmaos = Vec::new();
+        let mut indice = posicao;
+        while nivel.len() > 1 {
+            let vizinho = (indice ^ 1).min(nivel.len() - 1);
+            irmaos.push(hex::encode(nivel[vizinho]));
+            nivel = subir(&nivel);
+            indice /= 2;
+        }
+
+        Some(ProvaMerkle { txid: txids[posicao].clone(), posicao, irmaos })
+    }
+
+    /// Refaz o caminho até a raiz e compara com `raiz`
+    pub fn verificar(&self, raiz: &str) -> bool {
+        let mut atual = folha(&self.txid);
+        let mut indice = self.posicao;
+        for irmao in &self.irmaos {
+            let irmao: No = match hex::decode(irmao).ok().and_then(|b| b.try_into().ok()) {
+                Some(n) => n,
+                None => return false,
+            };
+            // Só o último nó de um nível ímpar é pareado consigo mesmo, e ele
+            // fica à esquerda: à direita de uma cópia, a posição seria fantasma
+            if !indice.is_multiple_of(2) && irmao == atual {
+                return false;
+            }
+            atual = if indice.is_multiple_of(2) { juntar(&atual, &irmao) } else { juntar(&irmao, &atual) };
+            indice /= 2;
+        }
+        indice == 0 && hex::encode(atual) == raiz
+    }
+}
+
+/// Raiz de Merkle dos txids, na ordem em que aparecem no bloco. Em níveis
+/// ímpares o último nó é pareado consigo mesmo.
+pub fn raiz_merkle(txids: &[String]) -> String {
+    if txids.is_empty() {
+        return RAIZ_VAZIA.to_string();
+    }
+    let mut nivel: Vec<No> = txids.iter().map(|t| folha(t)).collect();
+    while nivel.len() > 1 {
+        nivel = subir(&nivel);
+    }
+    hex::encode(nivel[0])
+}
+
+/// Folha da árvore: os 32 bytes do txid (ou o SHA256 do texto, se não for hex)
+fn folha(txid: &str) -> No {
+    hex::decode(txid)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .unwrap_or_else(|| Sha256::digest(txid.as_bytes()).into())
+}
+
+/// SHA256 duplo da concatenação de dois nós
+fn juntar(esquerda: &No, direita: &No) -> No {
+    let mut hasher = Sha256::new();
+    hasher.update(esquerda);
+    hasher.update(direita);
+    Sha256::digest(hasher.finalize()).into()
+}
+
+fn subir(nivel: &[No]) -> Vec<No> {
+    nivel
+        .chunks(2)
+        .map(|par| juntar(&par[0], par.get(1).unwrap_or(&par[0])))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txids(n: usize) -> Vec<String> {
+        (0..n).map(|i| hex::encode(Sha256::digest([i as u8]))).collect()
+    }
+
+    #[test]
+    fn raiz_com_nivel_impar_repete_o_ultimo_no() {
+        let ids = txids(3);
+        let [a, b, c] = [folha(&ids[0]), folha(&ids[1]), folha(&ids[2])];
+        let esperada = juntar(&juntar(&a, &b), &juntar(&c, &c));
+        assert_eq!(raiz_merkle(&ids), hex::encode(esperada));
+        assert_eq!(raiz_merkle(&ids[..1]), ids[0]);
+        assert_eq!(raiz_merkle(&[]), RAIZ_VAZIA);
+    }
+
+    #[test]
+    fn prova_de_cada_posicao_confere_com_a_raiz() {
+        for n in 1..=9 {
+            let ids = txids(n);
+            let raiz = raiz_merkle(&ids);
+            for posicao in 0..n {
+                let prova = ProvaMerkle::gerar(&ids, posicao).unwrap();
+                assert_eq!(prova.txid, ids[posicao]);
+                assert!(prova.verificar(&raiz), "{} folhas, posição {}", n, posicao);
+            }
+            assert!(ProvaMerkle::gerar(&ids, n).is_none());
+        }
+    }
+
+    #[test]
+    fn ultima_folha_de_nivel_impar_tem_a_si_mesma_como_irma() {
+        let ids = txids(5);
+        let prova = ProvaMerkle::gerar(&ids, 4).unwrap();
+        // 5 -> 3 -> 2 -> 1: três níveis, os dois primeiros com o nó repetido
+        assert_eq!(prova.irmaos.len(), 3);
+        assert_eq!(prova.irmaos[0], ids[4]);
+        assert!(prova.verificar(&raiz_merkle(&ids)));
+    }
+
+    #[test]
+    fn recusa_posicao_errada() {
+        let ids = txids(5);
+        let raiz = raiz_merkle(&ids);
+        for posicao in 0..ids.len() {
+            let prova = ProvaMerkle::gerar(&ids, posicao).unwrap();
+            for outra in (0..8).filter(|&p| p != posicao) {
+                let errada = ProvaMerkle { posicao: outra, ..prova.clone() };
+                assert!(!errada.verificar(&raiz), "posição {} aceita como {}", posicao, outra);
+            }
+        }
+    }
+
+    #[test]
+    fn recusa_prova_adulterada() {
+        let ids = txids(4);
+        let raiz = raiz_merkle(&ids);
+        let prova = ProvaMerkle::gerar(&ids, 1).unwrap();
+
+        let outro_txid = ProvaMerkle { txid: ids[2].clone(), ..prova.clone() };
+        assert!(!outro_txid.verificar(&raiz));
+
+        let mut irmao_trocado = prova.clone();
+        irmao_trocado.irmaos[1] = ids[3].clone();
+        assert!(!irmao_trocado.verificar(&raiz));
+
+        let mut sem_nivel = prova.clone();
+        sem_nivel.irmaos.pop();
+        assert!(!sem_nivel.verificar(&raiz));
+
+        let mut irmao_invalido = prova;
+        irmao_invalido.irmaos[0] = "zz".to_string();
+        assert!(!irmao_invalido.verificar(&raiz));
+    }
+}
@@ -0,0 +1,258 @@
+// src/miner.rs
+
+use crate::block::Block;
+use crate::codificacao::codificar;
+use crate::blockchain::{Blockchain, TAMANHO_MAX_BLOCO};
+use crate::mempool::Mempool;
+use crate::p2p::{conectar_bloco, P2PServer};
+use crate::relogio::{Relogio, TempoRede};
+use crate::transaction::{extra_nonce_coinbase, Transaction};
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Nonces tentados com cada extra-nonce antes de trocar a coinbase (32 bits, como no Bitcoin)
+pub const NONCES_POR_RODADA: u64 = 1 << 32;
+
+/// Parte do bloco reservada para provas de vida (o resto fica para as transações)
+const ESPACO_PROVAS_DE_VIDA: usize = TAMANHO_MAX_BLOCO / 10;
+
+/// De quanto em quanto tempo a mineração confere se o topo da cadeia mudou
+const INTERVALO_VERIFICACAO: Duration = Duration::from_millis(100);
+
+/// Minerador: monta o modelo do próximo bloco a partir da mempool e procura a
+/// prova de trabalho em vários threads, sem segurar o lock da blockchain
+#[derive(Clone)]
+pub struct Minerador {
+    blockchain: Arc<Mutex<Blockchain>>,
+    mempool: Arc<Mutex<Mempool>>,
+    servidor: Option<P2PServer>, // com servidor, o bloco achado também é anunciado aos peers
+    threads: usize,
+    nonces_por_rodada: u64,
+}
+
+/// Mineração em andamento em segundo plano
+pub struct Mineracao {
+    parar: Arc<AtomicBool>,
+    hashes: Arc<AtomicU64>,
+    inicio: Instant,
+    tarefa: JoinHandle<Option<Block>>,
+}
+
+impl Minerador {
+    /// Minerador sem rede, com um thread por núcleo disponível
+    pub fn new(blockchain: Arc<Mutex<Blockchain>>, mempool: Arc<Mutex<Mempool>>) -> Self {
+        Self::com_parametros(blockchain, mempool, None, threads_disponiveis(), NONCES_POR_RODADA)
+    }
+
+    /// Minerador do nó de `servidor`, que anuncia aos peers os blocos que achar
+    pub fn com_servidor(servidor: &P2PServer) -> Self {
+        Self::com_parametros(
+            Arc::clone(servidor.blockchain()),
+            Arc::clone(servidor.mempool()),
+            Some(servidor.clone()),
+            threads_disponiveis(),
+            NONCES_POR_RODADA,
+        )
+    }
+
+    /// Minerador com servidor, quantidade de threads e tamanho de rodada personalizados
+    pub fn com_parametros(
+        blockchain: Arc<Mutex<Blockchain>>,
+        mempool: Arc<Mutex<Mempool>>,
+        servidor: Option<P2PServer>,
+        threads: usize,
+        nonces_por_rodada: u64,
+    ) -> Self {
+        Minerador {
+            blockchain,
+            mempool,
+            servidor,
+            threads: threads.max(1),
+            nonces_por_rodada: nonces_por_rodada.max(1),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Modelo do próximo bloco: as provas de vida pendentes e as transações de
+    /// maior taxa da mempool, que continuam lá até o bloco ser conectado
+    pub fn modelo(&self, endereco_minerador: &str) -> Block {
+        let mut bc = self.blockchain.lock().unwrap();
+        let mempool = self.mempool.lock().unwrap();
+        let provas = mempool.provas_para_bloco(ESPACO_PROVAS_DE_VIDA);
+        let espaco_provas: usize = provas.iter().map(|p| codificar(p).len()).sum();
+        let txs: Vec<Transaction> = mempool.selecionar_para_bloco(TAMANHO_MAX_BLOCO - espaco_provas);
+        drop(mempool);
+        bc.montar_bloco_com_provas(txs, provas, endereco_minerador)
+    }
+
+    /// Começa a minerar um bloco para `endereco_minerador` em segundo plano. A
+    /// mineração termina ao achar o bloco (que é então conectado à cadeia), ao
+    /// ser cancelada ou quando o topo muda (um bloco chegou da rede) e o modelo
+    /// deixa de servir.
+    pub fn iniciar(&self, endereco_minerador: &str) -> Mineracao {
+        let modelo = self.modelo(endereco_minerador);
+        let parar = Arc::new(AtomicBool::new(false));
+        let hashes = Arc::new(AtomicU64::new(0));
+        let inicio = Instant::now();
+
+        let tarefa = {
+            let minerador = self.clone();
+            let parar = Arc::clone(&parar);
+            let hashes = Arc::clone(&hashes);
+            thread::spawn(move || {
+                let bloco = minerador.supervisionar(modelo, &parar, &hashes)?;
+                let taxa = hashes.load(Ordering::Relaxed) as f64 / inicio.elapsed().as_secs_f64().max(0.001);
+                minerador.entregar(bloco, taxa)
+            })
+        };
+
+        Mineracao { parar, hashes, inicio, tarefa }
+    }
+
+    /// Minera um bloco e espera o resultado (`None` se o topo mudou no caminho)
+    pub fn minerar(&self, endereco_minerador: &str) -> Option<Block> {
+        self.iniciar(endereco_minerador).aguardar()
+    }
+
+    /// Sobe os threads de busca e acompanha o topo da cadeia até alguém achar o bloco
+    fn supervisionar(&self, modelo: Block, parar: &AtomicBool, hashes: &AtomicU64) -> Option<Block> {
+        let rodada = self.nonces_por_rodada;
+        let tempo = Arc::clone(self.blockchain.lock().unwrap().tempo_rede());
+        let proximo_extra = AtomicU64::new(0);
+        let (encontrado, recebido) = mpsc::channel();
+
+        thread::scope(|escopo| {
+            for _ in 0..self.threads {
+                let encontrado = encontrado.clone();
+                let (modelo, proximo_extra, tempo) = (&modelo, &proximo_extra, &*tempo);
+                escopo.spawn(move || {
+                    if let Some(bloco) = procurar(modelo, proximo_extra, rodada, tempo, parar, hashes) {
+                        let _ = encontrado.send(bloco);
+                    }
+                });
+            }
+            drop(encontrado);
+
+            let resultado = loop {
+                match recebido.recv_timeout(INTERVALO_VERIFICACAO) {
+                    Ok(bloco) => break Some(bloco),
+                    Err(RecvTimeoutError::Disconnected) => break None,
+                    Err(RecvTimeoutError::Timeout) => {
+                        if parar.load(Ordering::Relaxed) {
+                            break None;
+                        }
+                        if self.blockchain.lock().unwrap().latest_hash() != modelo.header.previous_hash {
+                            println!("🔀 Novo topo na cadeia, mineração do bloco #{} cancelada", modelo.index);
+                            break None;
+                        }
+                    }
+                }
+            };
+            parar.store(true, Ordering::Relaxed);
+            resultado
+        })
+    }
+
+    /// Conecta um bloco já minerado (anunciando-o, se houver servidor); indica
+    /// se a cadeia ativa mudou
+    pub fn submeter(&self, bloco: Block) -> Result<bool, String> {
+        match &self.servidor {
+            Some(servidor) => servidor.submeter_bloco(bloco),
+            None => conectar_bloco(bloco, &self.blockchain, &self.mempool).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Conecta o bloco achado e o devolve se ele entrou na cadeia ativa
+    fn entregar(&self, bloco: Block, taxa_de_hash: f64) -> Option<Block> {
+        match self.submeter(bloco.clone()) {
+            Ok(true) => {
+                println!(
+                    "⛏️ Bloco #{} minerado com {} transações ({:.0} H/s)",
+                    bloco.index,
+                    bloco.transactions.len() - 1,
+                    taxa_de_hash
+                );
+                Some(bloco)
+            }
+            Ok(false) => None,
+            Err(e) => {
+                println!("❌ Bloco minerado #{} recusado: {}", bloco.index, e);
+                None
+            }
+        }
+    }
+}
+
+impl Mineracao {
+    /// Pede para os threads pararem; `aguardar` devolve `None` em seguida
+    pub fn cancelar(&self) {
+        self.parar.store(true, Ordering::Relaxed);
+    }
+
+    pub fn terminou(&self) -> bool {
+        self.tarefa.is_finished()
+    }
+
+    /// Total de hashes calculados até agora
+    pub fn hashes(&self) -> u64 {
+        self.hashes.load(Ordering::Relaxed)
+    }
+
+    /// Hashes por segundo desde o início da mineração
+    pub fn taxa_de_hash(&self) -> f64 {
+        let segundos = self.inicio.elapsed().as_secs_f64();
+        if segundos > 0.0 {
+            self.hashes() as f64 / segundos
+        } else {
+            0.0
+        }
+    }
+
+    /// Espera a mineração terminar e devolve o bloco encontrado, se houver
+    pub fn aguardar(self) -> Option<Block> {
+        self.tarefa.join().unwrap_or(None)
+    }
+}
+
+/// Laço de um thread: pega um extra-nonce ainda não usado, refaz a coinbase e
+/// percorre a rodada de nonces; esgotada a rodada, passa para o próximo extra-nonce
+fn procurar(
+    modelo: &Block,
+    proximo_extra: &AtomicU64,
+    rodada: u64,
+    tempo: &TempoRede,
+    parar: &AtomicBool,
+    hashes: &AtomicU64,
+) -> Option<Block> {
+    while !parar.load(Ordering::Relaxed) {
+        let mut bloco = modelo.clone();
+        aplicar_extra_nonce(&mut bloco, proximo_extra.fetch_add(1, Ordering::Relaxed));
+        bloco.header.timestamp = bloco.header.timestamp.max(tempo.agora());
+
+        if bloco.procurar_nonce(0..rodada, parar, hashes) {
+            return Some(bloco);
+        }
+    }
+    None
+}
+
+/// Grava o extra-nonce na entrada da coinbase (campo livre nela) e refaz a raiz
+/// de Merkle, o que dá ao cabeçalho um espaço de nonces novo
+pub(crate) fn aplicar_extra_nonce(bloco: &mut Block, extra: u64) {
+    let coinbase = &bloco.transactions[0];
+    let mut inputs = coinbase.inputs.clone();
+    inputs[0].pubkey = extra_nonce_coinbase(extra);
+    bloco.transactions[0] = Transaction::new(inputs, coinbase.outputs.clone());
+    bloco.header.merkle_root = bloco.calcular_raiz_merkle();
+}
+
+fn threads_disponiveis() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
@@ -1,117 +1,1189 @@
-use std::collections::HashSet;
-use std::net::{TcpListener, TcpStream};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::io::{Read, Write};
-
-use crate::blockchain::Blockchain;
-use crate::transaction::Transaction;
-
-/// Estrutura do servidor P2P
-#[derive(Clone)]
-pub struct P2PServer {
-    peers: Arc<Mutex<HashSet<String>>>, // lista de peers conectados
-}
-
-impl P2PServer {
-    pub fn get_peers(&self) -> Vec<String> {
-        self.peers.lock().unwrap().iter().cloned().collect() // ou outro tipo de retorno adequado
-    }
-
-    /// Envia um bloco JSON para um peer via stream TCP
-    pub fn enviar_bloco(&self, stream: &mut TcpStream, bloco_json: &str) {
-        let msg = format!("BLOCK:{}", bloco_json);
-        let _ = stream.write_all(msg.as_bytes());
-    }
-
-    /// Cria um novo servidor P2P
-    pub fn new() -> Self {
-        P2PServer {
-            peers: Arc::new(Mutex::new(HashSet::new())),
-        }
-    }
-
-    /// Itera sobre os peers conhecidos e aplica uma função
-    pub fn iter_peers<F>(&self, mut f: F)
-    where
-        F: FnMut(&String),
-    {
-        let peers = self.peers.lock().unwrap();
-        for addr in peers.iter() {
-            f(addr);
-        }
-    }
-
-    /// Inicia o servidor P2P em uma porta específica
-    pub fn start(&self, porta: u16, blockchain: Arc<Mutex<Blockchain>>) {
-        let listener = TcpListener::bind(("0.0.0.0", porta)).expect("Erro ao iniciar servidor P2P");
-        println!("🌐 Servidor P2P escutando na porta {}", porta);
-
-        for stream in listener.incoming() {
-            if let Ok(mut stream) = stream {
-                let blockchain_clone = Arc::clone(&blockchain);
-                thread::spawn(move || {
-                    P2PServer::handle_connection(&mut stream, blockchain_clone);
-                });
-            }
-        }
-    }
-
-    /// Trata cada conexão de peer
-    fn handle_connection(stream: &mut TcpStream, blockchain: Arc<Mutex<Blockchain>>) {
-        let mut buffer = vec![0; 1024];
-        match stream.read(&mut buffer) {
-            Ok(_) => {
-                let mensagem = String::from_utf8_lossy(&buffer);
-                if mensagem.contains("TRANSACTION:") {
-                    let json = mensagem.replace("TRANSACTION:", "").trim().to_string();
-                    match serde_json::from_str::<Transaction>(&json) {
-                        Ok(tx) => {
-                            println!("📨 Transação recebida via P2P: {:?}", tx);
-                            // TODO: adicionar à fila de transações pendentes
-                        }
-                        Err(e) => println!("❌ Erro ao desserializar transação: {}", e),
-                    }
-                } else if mensagem.contains("BLOCK:") {
-                    let json = mensagem.replace("BLOCK:", "").trim().to_string();
-                    // TODO: validar e adicionar bloco à blockchain
-                    println!("📦 Bloco recebido via P2P: {}", json);
-                }
-            }
-            Err(e) => {
-                println!("❌ Erro ao ler do stream: {}", e);
-            }
-        }
-    }
-
-    /// Envia uma transação para um peer remoto
-    pub fn enviar_transacao(&self, endereco: &str, tx: &Transaction) {
-        if let Ok(mut stream) = TcpStream::connect(endereco) {
-            let json = serde_json::to_string(tx).unwrap();
-            let mensagem = format!("TRANSACTION:{}", json);
-            let _ = stream.write_all(mensagem.as_bytes());
-            println!("📤 Transação enviada para {}", endereco);
-        } else {
-            println!("⚠️ Falha ao conectar com peer {}", endereco);
-        }
-    }
-
-    /// Lista os peers conectados
-    pub fn listar_peers(&self) {
-        let peers = self.peers.lock().unwrap();
-        println!("🔗 Peers conectados:");
-        for peer in peers.iter() {
-            println!("- {}", peer);
-        }
-    }
-
-    /// Conecta-se a um novo peer e o adiciona à lista
-    pub fn conectar_a_peer(&self, endereco: &str) {
-        let mut peers = self.peers.lock().unwrap();
-        if peers.insert(endereco.to_string()) {
-            println!("✅ Conectado ao novo peer: {}", endereco);
-        } else {
-            println!("ℹ️ Já conectado ao peer: {}", endereco);
-        }
-    }
-}
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use rand::Rng;
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::{oneshot, watch, Notify};
+use tokio::time;
+
+use crate::block::Block;
+use crate::blockchain::{Blockchain, ErroBloco};
+use crate::mempool::Mempool;
+use crate::peerdb::{BancoPeers, EnderecoPeer, ListaBanidos, ARQUIVO_BANIDOS, ARQUIVO_PEERS};
+use crate::protocol::{
+    escrever_mensagem_async, ler_mensagem_async, Decodificador, ErroProtocolo, Message,
+    ResumoBloco, StatusNo, VersaoNo, USER_AGENT, VERSAO_NO, VERSAO_NO_MINIMA,
+};
+use crate::transaction::{ProvaDeVida, Transaction};
+
+/// Porta usada quando nenhuma é configurada
+pub const PORTA_PADRAO: u16 = 6010;
+
+/// Máximo de conexões de saída mantidas
+const MAX_SAIDA: usize = 8;
+
+/// Máximo de conexões de entrada aceitas
+const MAX_ENTRADA: usize = 32;
+
+/// Máximo de endereços em uma mensagem ADDR
+const MAX_ADDR: usize = 1000;
+
+/// ADDR com até essa quantidade de endereços é repassado aos demais peers
+const MAX_ADDR_REPASSE: usize = 10;
+
+/// Erro de handshake quando o nonce recebido é o nosso
+const CONEXAO_PROPRIA: &str = "conexão consigo mesmo";
+
+/// Intervalo entre rodadas de busca por novos peers
+const INTERVALO_DESCOBERTA: Duration = Duration::from_secs(10);
+
+/// Máximo de cabeçalhos devolvidos por GET_HEADERS
+const MAX_CABECALHOS: usize = 2000;
+
+/// Máximo de blocos pedidos/devolvidos por GET_BLOCKS
+const LOTE_BLOCOS: usize = 16;
+
+/// Intervalo entre pings enviados a cada peer
+const INTERVALO_PING: Duration = Duration::from_secs(30);
+
+/// Sem nenhuma mensagem por esse tempo, o peer é considerado desconectado
+const TIMEOUT_INATIVIDADE: i64 = 90;
+
+/// Prazo para concluir o handshake VERSION/VERACK
+const TIMEOUT_HANDSHAKE: Duration = Duration::from_secs(10);
+
+/// Prazo para um peer responder a uma requisição
+const TIMEOUT_RESPOSTA: Duration = Duration::from_secs(30);
+
+/// Prazo para escrever uma mensagem no socket de um peer
+const TIMEOUT_ESCRITA: Duration = Duration::from_secs(30);
+
+/// Mensagens aguardando envio a um peer; com a fila cheia os anúncios
+/// para ele são descartados e as respostas esperam vaga
+const FILA_SAIDA: usize = 256;
+
+/// Respostas de um peer aguardando quem as requisitou
+const FILA_RESPOSTAS: usize = 16;
+
+/// Tempo dado às conexões para esvaziar as filas ao encerrar o servidor
+const PRAZO_ENCERRAMENTO: Duration = Duration::from_secs(5);
+
+/// Pontuação de mau comportamento a partir da qual o peer é banido
+const LIMITE_BANIMENTO: u32 = 100;
+
+/// Penalidade por bloco que viola as regras de consenso
+const PONTOS_BLOCO_INVALIDO: u32 = 100;
+
+/// Penalidade por transação inválida (assinatura, valores, coinbase avulsa)
+const PONTOS_TX_INVALIDA: u32 = 20;
+
+/// Penalidade por mensagem malformada ou fora de contexto
+const PONTOS_MENSAGEM_INVALIDA: u32 = 10;
+
+/// Penalidade por mensagem que excede o limite de taxa
+const PONTOS_EXCESSO_TAXA: u32 = 5;
+
+/// Janela usada para contar mensagens de cada conexão
+const JANELA_TAXA: Duration = Duration::from_secs(10);
+
+/// Máximo de mensagens aceitas de um peer por janela
+const MAX_MENSAGENS_JANELA: u32 = 200;
+
+/// Sentido em que a conexão foi aberta
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direcao {
+    Entrada,
+    Saida,
+}
+
+/// Dados de um peer conectado, obtidos no handshake
+#[derive(Debug, Clone)]
+pub struct InfoPeer {
+    pub endereco: String,
+    pub direcao: Direcao,
+    pub versao: u32,
+    pub altura: u64,
+    pub user_agent: String,
+    pub conectado_em: i64,
+    pub ultimo_contato: i64,
+    pub pontuacao: u32, // mau comportamento acumulado
+}
+
+/// Conexão ativa com um peer
+struct Peer {
+    info: InfoPeer,
+    saida: mpsc::Sender<Message>,    // fila da tarefa que escreve no socket
+    ultimo_contato: Arc<AtomicI64>,  // atualizado a cada mensagem recebida
+    pontuacao: AtomicU32,            // pontos de mau comportamento
+    respostas: Arc<tokio::sync::Mutex<mpsc::Receiver<Message>>>, // respostas a requisições feitas por nós
+    resposta_pendente: Arc<Mutex<Option<&'static str>>>, // comando da resposta que aguardamos do peer
+    desconectar: Arc<Notify>,        // acorda a tarefa de leitura para fechar a conexão
+}
+
+/// Conta as mensagens de uma conexão em janelas fixas de tempo
+struct LimiteTaxa {
+    inicio: Instant,
+    mensagens: u32,
+}
+
+impl LimiteTaxa {
+    fn new() -> Self {
+        LimiteTaxa { inicio: Instant::now(), mensagens: 0 }
+    }
+
+    /// Registra uma mensagem; `false` se a janela atual já estourou o limite
+    fn registrar(&mut self) -> bool {
+        if self.inicio.elapsed() >= JANELA_TAXA {
+            self.inicio = Instant::now();
+            self.mensagens = 0;
+        }
+        self.mensagens += 1;
+        self.mensagens <= MAX_MENSAGENS_JANELA
+    }
+}
+
+/// Estrutura do servidor P2P
+#[derive(Clone)]
+pub struct P2PServer {
+    porta: u16,
+    nonce: u64,         // identifica este nó no VERSION
+    diretorio: PathBuf, // onde ficam peers.json e banidos.json
+    blockchain: Arc<Mutex<Blockchain>>,
+    mempool: Arc<Mutex<Mempool>>,
+    peers: Arc<Mutex<HashMap<String, Peer>>>, // peers com handshake concluído
+    banco: Arc<Mutex<BancoPeers>>,            // endereços conhecidos, conectados ou não
+    banidos: Arc<Mutex<ListaBanidos>>,        // IPs banidos por mau comportamento (ver `chave_banimento`)
+    sincronizando: Arc<Mutex<HashSet<String>>>, // peers com sincronização em andamento
+    desligar: Arc<watch::Sender<bool>>,       // passa a `true` ao encerrar o servidor
+}
+
+impl P2PServer {
+    /// Cria um novo servidor P2P para a porta e o estado compartilhado informados
+    pub fn new(porta: u16, blockchain: Arc<Mutex<Blockchain>>, mempool: Arc<Mutex<Mempool>>) -> Self {
+        Self::com_diretorio(porta, blockchain, mempool, Path::new("."))
+    }
+
+    /// Cria um servidor que guarda seus arquivos em `diretorio`
+    pub fn com_diretorio(porta: u16, blockchain: Arc<Mutex<Blockchain>>, mempool: Arc<Mutex<Mempool>>, diretorio: &Path) -> Self {
+        let arquivo = |nome: &str| diretorio.join(nome).to_string_lossy().to_string();
+        let banco = BancoPeers::carregar_de_arquivo(&arquivo(ARQUIVO_PEERS)).unwrap_or_default();
+        let banidos = ListaBanidos::carregar_de_arquivo(&arquivo(ARQUIVO_BANIDOS)).unwrap_or_default();
+        let (desligar, _) = watch::channel(false);
+
+        P2PServer {
+            porta,
+            nonce: rand::thread_rng().gen(),
+            diretorio: diretorio.to_path_buf(),
+            blockchain,
+            mempool,
+            peers: Arc::new(Mutex::new(HashMap::new())),
+            banco: Arc::new(Mutex::new(banco)),
+            banidos: Arc::new(Mutex::new(banidos)),
+            sincronizando: Arc::new(Mutex::new(HashSet::new())),
+            desligar: Arc::new(desligar),
+        }
+    }
+
+    /// Caminho de um dos arquivos do nó
+    fn arquivo(&self, nome: &str) -> String {
+        self.diretorio.join(nome).to_string_lossy().to_string()
+    }
+
+    pub fn porta(&self) -> u16 {
+        self.porta
+    }
+
+    pub fn blockchain(&self) -> &Arc<Mutex<Blockchain>> {
+        &self.blockchain
+    }
+
+    pub fn mempool(&self) -> &Arc<Mutex<Mempool>> {
+        &self.mempool
+    }
+
+    /// Registra nós semente a serem usados na descoberta de peers
+    pub fn adicionar_seeds(&self, seeds: &[String]) {
+        let mut banco = self.banco.lock().unwrap();
+        for seed in seeds {
+            banco.adicionar(seed, Utc::now().timestamp());
+        }
+    }
+
+    /// Quantidade de conexões abertas no sentido informado
+    fn conexoes(&self, direcao: Direcao) -> usize {
+        self.peers
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|p| p.info.direcao == direcao)
+            .count()
+    }
+
+    pub fn get_peers(&self) -> Vec<String> {
+        self.peers.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Estado dos peers conectados
+    pub fn info_peers(&self) -> Vec<InfoPeer> {
+        self.peers
+            .lock()
+            .unwrap()
+            .values()
+            .map(|p| InfoPeer {
+                ultimo_contato: p.ultimo_contato.load(Ordering::Relaxed),
+                pontuacao: p.pontuacao.load(Ordering::Relaxed),
+                ..p.info.clone()
+            })
+            .collect()
+    }
+
+    /// Itera sobre os peers conhecidos e aplica uma função
+    pub fn iter_peers<F>(&self, mut f: F)
+    where
+        F: FnMut(&String),
+    {
+        for addr in self.get_peers() {
+            f(&addr);
+        }
+    }
+
+    /// Abre a porta e dispara as tarefas de aceite de conexões, keepalive e
+    /// descoberta de peers. Retorna assim que o servidor está escutando.
+    pub async fn start(&self) -> Result<(), String> {
+        let listener = TcpListener::bind(("0.0.0.0", self.porta))
+            .await
+            .map_err(|e| format!("Erro ao iniciar servidor P2P na porta {}: {}", self.porta, e))?;
+        println!("🌐 Servidor P2P escutando na porta {}", self.porta);
+
+        tokio::spawn(self.clone().aceitar_conexoes(listener));
+        tokio::spawn(self.clone().manter_conexoes());
+        tokio::spawn(self.clone().descobrir_peers());
+        Ok(())
+    }
+
+    /// Encerra o servidor: para de aceitar conexões, fecha as existentes
+    /// depois de enviar o que estava na fila e grava peers e banidos
+    pub async fn encerrar(&self) {
+        self.desligar.send_replace(true);
+
+        let prazo = Instant::now() + PRAZO_ENCERRAMENTO;
+        while !self.peers.lock().unwrap().is_empty() && Instant::now() < prazo {
+            time::sleep(Duration::from_millis(50)).await;
+        }
+
+        if let Err(e) = self.banco.lock().unwrap().salvar_em_arquivo(&self.arquivo(ARQUIVO_PEERS)) {
+            println!("⚠️ Falha ao salvar peers: {}", e);
+        }
+        if let Err(e) = self.banidos.lock().unwrap().salvar_em_arquivo(&self.arquivo(ARQUIVO_BANIDOS)) {
+            println!("⚠️ Falha ao salvar banidos: {}", e);
+        }
+        println!("👋 Servidor P2P da porta {} encerrado", self.porta);
+    }
+
+    /// Resolve quando o servidor começa a ser encerrado
+    async fn aguardar_encerramento(&self) {
+        let mut desligar = self.desligar.subscribe();
+        let _ = desligar.wait_for(|d| *d).await;
+    }
+
+    /// Aceita conexões de entrada até o servidor ser encerrado
+    async fn aceitar_conexoes(self, listener: TcpListener) {
+        loop {
+            let (stream, addr) = tokio::select! {
+                conexao = listener.accept() => match conexao {
+                    Ok(c) => c,
+                    Err(e) => {
+                        println!("⚠️ Erro ao aceitar conexão: {}", e);
+                        continue;
+                    }
+                },
+                _ = self.aguardar_encerramento() => return,
+            };
+
+            // Soltar o stream fecha a conexão
+            if self.esta_banido(&addr.to_string()) {
+                continue;
+            }
+            if self.conexoes(Direcao::Entrada) >= MAX_ENTRADA {
+                println!("⚠️ Limite de {} conexões de entrada atingido, recusando", MAX_ENTRADA);
+                continue;
+            }
+
+            tokio::spawn(self.clone().executar_conexao(stream, addr.to_string(), Direcao::Entrada, None));
+        }
+    }
+
+    /// Abre uma conexão de saída e aguarda o handshake
+    pub async fn conectar_a_peer(&self, endereco: &str) -> Result<(), String> {
+        if self.peers.lock().unwrap().contains_key(endereco) {
+            println!("ℹ️ Já conectado ao peer: {}", endereco);
+            return Ok(());
+        }
+        if self.esta_banido(endereco) {
+            return Err("peer banido".to_string());
+        }
+
+        self.banco.lock().unwrap().adicionar(endereco, 0);
+        let stream = match time::timeout(TIMEOUT_HANDSHAKE, TcpStream::connect(endereco)).await {
+            Ok(Ok(s)) => s,
+            Ok(Err(e)) => {
+                self.banco.lock().unwrap().marcar_falha(endereco);
+                return Err(format!("falha ao conectar: {}", e));
+            }
+            Err(_) => {
+                self.banco.lock().unwrap().marcar_falha(endereco);
+                return Err("tempo esgotado ao conectar".to_string());
+            }
+        };
+
+        // A tarefa da conexão avisa quando o handshake termina
+        let (pronto, handshake) = oneshot::channel();
+        tokio::spawn(self.clone().executar_conexao(stream, endereco.to_string(), Direcao::Saida, Some(pronto)));
+        handshake
+            .await
+            .unwrap_or_else(|_| Err("conexão encerrada durante o handshake".to_string()))
+    }
+
+    /// Conduz o handshake e depois processa as mensagens do peer até ele desconectar
+    async fn executar_conexao(
+        self,
+        stream: TcpStream,
+        endereco: String,
+        direcao: Direcao,
+        pronto: Option<oneshot::Sender<Result<(), String>>>,
+    ) {
+        let addr_remoto = stream.peer_addr().ok();
+        let (mut leitura, mut escrita) = stream.into_split();
+        let mut decodificador = Decodificador::new();
+
+        let handshake = self.handshake(&mut leitura, &mut escrita, &mut decodificador);
+        let versao = match time::timeout(TIMEOUT_HANDSHAKE, handshake)
+            .await
+            .unwrap_or_else(|_| Err("tempo esgotado".to_string()))
+        {
+            Ok(v) => v,
+            Err(e) => {
+                println!("❌ Handshake com {} falhou: {}", endereco, e);
+                if direcao == Direcao::Saida {
+                    let mut banco = self.banco.lock().unwrap();
+                    if e == CONEXAO_PROPRIA {
+                        banco.marcar_proprio(&endereco);
+                    } else {
+                        banco.marcar_falha(&endereco);
+                    }
+                }
+                if let Some(pronto) = pronto {
+                    let _ = pronto.send(Err(e));
+                }
+                return;
+            }
+        };
+
+        // Conexões de entrada são identificadas pela porta em que o peer escuta
+        let endereco = match (direcao, addr_remoto) {
+            (Direcao::Entrada, Some(addr)) => SocketAddr::new(addr.ip(), versao.porta_escuta).to_string(),
+            _ => endereco,
+        };
+        // Em loopback o banimento é por porta, que só se conhece depois do handshake
+        if direcao == Direcao::Entrada && self.esta_banido(&endereco) {
+            return;
+        }
+
+        // A hora informada pelo peer entra na hora ajustada pela rede
+        self.blockchain.lock().unwrap().tempo_rede().registrar(&ip_de(&endereco), versao.timestamp);
+
+        let agora = Utc::now().timestamp();
+        let ultimo_contato = Arc::new(AtomicI64::new(agora));
+        let desconectar = Arc::new(Notify::new());
+        let (saida, fila_saida) = mpsc::channel(FILA_SAIDA);
+        let (tx_respostas, rx_respostas) = mpsc::channel(FILA_RESPOSTAS);
+        let resposta_pendente = Arc::new(Mutex::new(None));
+        {
+            let mut peers = self.peers.lock().unwrap();
+            if peers.contains_key(&endereco) {
+                println!("ℹ️ Conexão duplicada com {} descartada", endereco);
+                if let Some(pronto) = pronto {
+                    let _ = pronto.send(Ok(()));
+                }
+                return;
+            }
+            peers.insert(
+                endereco.clone(),
+                Peer {
+                    info: InfoPeer {
+                        endereco: endereco.clone(),
+                        direcao,
+                        versao: versao.versao,
+                        altura: versao.altura,
+                        user_agent: versao.user_agent.clone(),
+                        conectado_em: agora,
+                        ultimo_contato: agora,
+                        pontuacao: 0,
+                    },
+                    saida: saida.clone(),
+                    ultimo_contato: Arc::clone(&ultimo_contato),
+                    pontuacao: AtomicU32::new(0),
+                    respostas: Arc::new(tokio::sync::Mutex::new(rx_respostas)),
+                    resposta_pendente: Arc::clone(&resposta_pendente),
+                    desconectar: Arc::clone(&desconectar),
+                },
+            );
+        }
+        tokio::spawn(escrever_mensagens(escrita, fila_saida, Arc::clone(&desconectar)));
+        println!(
+            "✅ Conectado ao peer {} ({}, altura {})",
+            endereco, versao.user_agent, versao.altura
+        );
+        if let Some(pronto) = pronto {
+            let _ = pronto.send(Ok(()));
+        }
+
+        match direcao {
+            Direcao::Saida => {
+                self.banco.lock().unwrap().marcar_sucesso(&endereco);
+                let _ = saida.send(Message::GetAddr).await;
+            }
+            Direcao::Entrada => {
+                // Um peer novo que nos procurou é anunciado aos demais
+                if self.banco.lock().unwrap().adicionar(&endereco, agora) {
+                    let addr = vec![EnderecoPeer { endereco: endereco.clone(), visto_em: agora }];
+                    self.transmitir_exceto(&Message::Addr(addr), &endereco);
+                }
+            }
+        }
+
+        if versao.altura > self.blockchain.lock().unwrap().height() {
+            self.sincronizar_em_segundo_plano(&endereco);
+        }
+
+        self.ler_mensagens(
+            &endereco,
+            &mut leitura,
+            &mut decodificador,
+            &ultimo_contato,
+            &saida,
+            &tx_respostas,
+            &resposta_pendente,
+            &desconectar,
+        )
+        .await;
+
+        // Sem o peer no mapa a fila de saída fecha e a tarefa de escrita
+        // termina depois de enviar o que já estava na fila
+        self.peers.lock().unwrap().remove(&endereco);
+        println!("🔌 Peer {} desconectado", endereco);
+    }
+
+    /// Troca VERSION/VERACK com o peer e devolve a versão anunciada por ele
+    async fn handshake(&self, leitura: &mut OwnedReadHalf, escrita: &mut OwnedWriteHalf, decodificador: &mut Decodificador) -> Result<VersaoNo, String> {
+        let nossa_versao = {
+            let bc = self.blockchain.lock().unwrap();
+            VersaoNo {
+                versao: VERSAO_NO,
+                altura: bc.height(),
+                topo: bc.latest_hash(),
+                user_agent: USER_AGENT.to_string(),
+                porta_escuta: self.porta,
+                nonce: self.nonce,
+                timestamp: bc.tempo_rede().local(),
+            }
+        };
+        escrever_mensagem_async(escrita, &Message::Version(nossa_versao))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let mut versao_peer: Option<VersaoNo> = None;
+        let mut recebeu_verack = false;
+
+        while versao_peer.is_none() || !recebeu_verack {
+            match ler_mensagem_async(leitura, decodificador).await {
+                Ok(Some(Message::Version(v))) if versao_peer.is_none() => {
+                    if v.nonce == self.nonce {
+                        return Err(CONEXAO_PROPRIA.to_string());
+                    }
+                    if v.versao < VERSAO_NO_MINIMA {
+                        return Err(format!("versão {} obsoleta", v.versao));
+                    }
+                    escrever_mensagem_async(escrita, &Message::Verack)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    versao_peer = Some(v);
+                }
+                Ok(Some(Message::Verack)) => recebeu_verack = true,
+                Ok(Some(outra)) => return Err(format!("{} recebido antes do handshake", outra.comando())),
+                Ok(None) => return Err("peer fechou a conexão".to_string()),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        Ok(versao_peer.unwrap())
+    }
+
+    /// Laço principal de uma conexão já estabelecida
+    #[allow(clippy::too_many_arguments)]
+    async fn ler_mensagens(
+        &self,
+        endereco: &str,
+        leitura: &mut OwnedReadHalf,
+        decodificador: &mut Decodificador,
+        ultimo_contato: &AtomicI64,
+        saida: &mpsc::Sender<Message>,
+        respostas: &mpsc::Sender<Message>,
+        resposta_pendente: &Mutex<Option<&'static str>>,
+        desconectar: &Notify,
+    ) {
+        let mut limite = LimiteTaxa::new();
+        loop {
+            let lido = tokio::select! {
+                lido = ler_mensagem_async(leitura, decodificador) => lido,
+                _ = desconectar.notified() => return,
+                _ = self.aguardar_encerramento() => return,
+            };
+
+            let mensagem = match lido {
+                Ok(Some(m)) => m,
+                Ok(None) => return,
+                Err(ErroProtocolo::Io(_)) => return,
+                // O quadro já foi consumido: dá para seguir lendo o próximo
+                Err(e @ (ErroProtocolo::ChecksumInvalido | ErroProtocolo::PayloadInvalido(_))) => {
+                    self.penalizar(endereco, PONTOS_MENSAGEM_INVALIDA, &e.to_string());
+                    continue;
+                }
+                Err(ErroProtocolo::ComandoDesconhecido(c)) => {
+                    println!("ℹ️ Comando desconhecido '{}' ignorado de {}", c, endereco);
+                    continue;
+                }
+                // Cabeçalho corrompido: o fluxo perdeu o alinhamento
+                Err(e) => {
+                    println!("❌ Mensagem inválida recebida de {}: {}", endereco, e);
+                    self.penalizar(endereco, PONTOS_MENSAGEM_INVALIDA, &e.to_string());
+                    return;
+                }
+            };
+            ultimo_contato.store(Utc::now().timestamp(), Ordering::Relaxed);
+
+            // Só a resposta à requisição pendente escapa do limite de taxa; as não
+            // pedidas contam nele e caem em `processar_mensagem`, que pune o peer
+            let solicitada = resposta_pendente
+                .lock()
+                .unwrap()
+                .take_if(|comando| *comando == mensagem.comando())
+                .is_some();
+            if !solicitada && !limite.registrar() {
+                self.penalizar(endereco, PONTOS_EXCESSO_TAXA, "excesso de mensagens");
+                continue;
+            }
+
+            match mensagem {
+                // Respostas a requisições nossas seguem para quem as aguarda
+                Message::Status(_) | Message::Headers(_) | Message::Blocks(_) if solicitada => {
+                    let _ = respostas.try_send(mensagem);
+                }
+                Message::Pong(_) => {}
+                Message::Block(bloco) => self.receber_bloco(endereco, bloco).await,
+                outra => {
+                    if let Some(resposta) = self.processar_mensagem(endereco, outra) {
+                        // Responder espera vaga na fila: é a contrapressão sobre o peer
+                        if saida.send(resposta).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Trata uma mensagem recebida, devolvendo a resposta quando houver
+    fn processar_mensagem(&self, origem: &str, mensagem: Message) -> Option<Message> {
+        match mensagem {
+            Message::Ping(nonce) => Some(Message::Pong(nonce)),
+            Message::GetAddr => Some(Message::Addr(self.banco.lock().unwrap().para_anunciar(MAX_ADDR))),
+            Message::Addr(enderecos) => {
+                if enderecos.len() > MAX_ADDR {
+                    self.penalizar(origem, PONTOS_MENSAGEM_INVALIDA, &format!("ADDR com {} endereços", enderecos.len()));
+                    return None;
+                }
+
+                let agora = Utc::now().timestamp();
+                let novos: Vec<EnderecoPeer> = {
+                    let mut banco = self.banco.lock().unwrap();
+                    enderecos
+                        .into_iter()
+                        .filter(|a| a.endereco.parse::<SocketAddr>().is_ok())
+                        .filter(|a| banco.adicionar(&a.endereco, a.visto_em.min(agora)))
+                        .collect()
+                };
+
+                if !novos.is_empty() {
+                    println!("📒 {} endereço(s) novo(s) aprendido(s) de {}", novos.len(), origem);
+                    if novos.len() <= MAX_ADDR_REPASSE {
+                        self.transmitir_exceto(&Message::Addr(novos), origem);
+                    }
+                }
+                None
+            }
+            Message::Transaction(tx) => {
+                println!("📨 Transação recebida via P2P: {}", tx.id);
+                // Mesma ordem de travas do resto do nó: blockchain antes da mempool
+                let bc = self.blockchain.lock().unwrap();
+                let utxo_set = bc.utxos();
+                let txid = tx.id.clone();
+                let mut mempool = self.mempool.lock().unwrap();
+
+                // Inputs já gastos ou desconhecidos podem ser só atraso de propagação
+                let conflito = mempool.contem(&txid)
+                    || tx.inputs.iter().any(|i| {
+                        utxo_set.get(&i.outpoint()).is_none() || mempool.esta_gasto(&i.outpoint())
+                    });
+
+                match mempool.adicionar(tx.clone(), utxo_set) {
+                    Ok(taxa) => {
+                        println!("📥 Transação {} adicionada à mempool (taxa {})", txid, taxa);
+                        drop(mempool);
+                        drop(bc);
+                        self.transmitir_exceto(&Message::Transaction(tx), origem);
+                    }
+                    Err(e) => {
+                        println!("❌ Transação {} rejeitada: {}", txid, e);
+                        drop(mempool);
+                        drop(bc);
+                        if !conflito {
+                            self.penalizar(origem, PONTOS_TX_INVALIDA, &format!("transação inválida: {}", e));
+                        }
+                    }
+                }
+                None
+            }
+            Message::ProvaDeVida(prova) => {
+                let id = prova.id();
+                let resultado = {
+                    let bc = self.blockchain.lock().unwrap();
+                    let mut mempool = self.mempool.lock().unwrap();
+                    if mempool.contem_prova(&id) {
+                        return None;
+                    }
+                    mempool.adicionar_prova(prova.clone(), bc.utxos())
+                };
+                // Recusa não pune o peer: a prova pode só ter vencido ou sido confirmada no caminho
+                match resultado {
+                    Ok(()) => {
+                        println!("💓 Prova de vida {} adicionada à mempool ({} outputs)", id, prova.outpoints.len());
+                        self.transmitir_exceto(&Message::ProvaDeVida(prova), origem);
+                    }
+                    Err(e) => println!("❌ Prova de vida {} rejeitada: {}", id, e),
+                }
+                None
+            }
+            Message::GetStatus => {
+                let bc = self.blockchain.lock().unwrap();
+                Some(Message::Status(StatusNo { altura: bc.height(), topo: bc.latest_hash() }))
+            }
+            Message::GetHeaders(localizador) => {
+                let bc = self.blockchain.lock().unwrap();
+                let resumos = bc
+                    .entradas_apos(&localizador, MAX_CABECALHOS)
+                    .into_iter()
+                    .map(|e| ResumoBloco {
+                        index: e.index,
+                        hash: e.hash.clone(),
+                        previous_hash: e.previous_hash.clone(),
+                    })
+                    .collect();
+                Some(Message::Headers(resumos))
+            }
+            Message::GetBlocks(hashes) => {
+                let bc = self.blockchain.lock().unwrap();
+                let blocos = hashes
+                    .iter()
+                    .take(LOTE_BLOCOS)
+                    .filter_map(|h| bc.bloco_por_hash(h))
+                    .collect();
+                Some(Message::Blocks(blocos))
+            }
+            outra => {
+                self.penalizar(origem, PONTOS_MENSAGEM_INVALIDA, &format!("{} inesperada", outra.comando()));
+                None
+            }
+        }
+    }
+
+    /// Envia pings periódicos e derruba peers que pararam de responder
+    async fn manter_conexoes(self) {
+        let mut intervalo = time::interval(INTERVALO_PING);
+        intervalo.tick().await; // o primeiro tick é imediato
+
+        loop {
+            tokio::select! {
+                _ = intervalo.tick() => {}
+                _ = self.aguardar_encerramento() => return,
+            }
+            let agora = Utc::now().timestamp();
+
+            let mut banidos = self.banidos.lock().unwrap();
+            if banidos.remover_expirados(agora) > 0 {
+                if let Err(e) = banidos.salvar_em_arquivo(&self.arquivo(ARQUIVO_BANIDOS)) {
+                    println!("⚠️ Falha ao salvar banidos: {}", e);
+                }
+            }
+            drop(banidos);
+
+            let peers = self.peers.lock().unwrap();
+            for (endereco, peer) in peers.iter() {
+                let ultimo = peer.ultimo_contato.load(Ordering::Relaxed);
+                if agora - ultimo > TIMEOUT_INATIVIDADE {
+                    println!("⏱️ Peer {} inativo há {}s, desconectando", endereco, agora - ultimo);
+                    peer.desconectar.notify_one();
+                } else {
+                    // Com a fila cheia o peer já tem tráfego; o ping pode ficar para depois
+                    let _ = peer.saida.try_send(Message::Ping(rand::thread_rng().gen()));
+                }
+            }
+        }
+    }
+
+    /// Mantém as conexões de saída preenchidas com endereços do banco de peers
+    async fn descobrir_peers(self) {
+        let mut intervalo = time::interval(INTERVALO_DESCOBERTA);
+        loop {
+            tokio::select! {
+                _ = intervalo.tick() => {}
+                _ = self.aguardar_encerramento() => return,
+            }
+
+            let vagas = MAX_SAIDA.saturating_sub(self.conexoes(Direcao::Saida));
+            if vagas > 0 {
+                let conectados = self.get_peers();
+                let candidatos = self.banco.lock().unwrap().candidatos(&conectados, vagas);
+                for endereco in candidatos.into_iter().filter(|e| !self.esta_banido(e)) {
+                    if let Err(e) = self.conectar_a_peer(&endereco).await {
+                        println!("⚠️ Falha ao conectar ao peer {}: {}", endereco, e);
+                    }
+                }
+            }
+
+            if let Err(e) = self.banco.lock().unwrap().salvar_em_arquivo(&self.arquivo(ARQUIVO_PEERS)) {
+                println!("⚠️ Falha ao salvar peers: {}", e);
+            }
+        }
+    }
+
+    /// Valida um bloco recebido, conecta-o à cadeia, persiste e repassa aos peers
+    async fn receber_bloco(&self, origem: &str, bloco: Block) {
+        // Blocos já conhecidos são ecos do nosso próprio anúncio
+        if self.blockchain.lock().unwrap().conhece_bloco(&bloco.hash) {
+            return;
+        }
+        println!("📦 Bloco #{} recebido via P2P: {}", bloco.index, bloco.hash);
+
+        // Sem o bloco anterior não dá para julgar: pede o que falta ao peer
+        if !self.blockchain.lock().unwrap().conhece_bloco(&bloco.header.previous_hash) {
+            println!("🔍 Bloco #{} sem antecessor conhecido, sincronizando com {}", bloco.index, origem);
+            self.sincronizar_em_segundo_plano(origem);
+            return;
+        }
+
+        match conectar_bloco_em_segundo_plano(bloco.clone(), &self.blockchain, &self.mempool).await {
+            Ok(true) => {
+                println!("✅ Bloco #{} adicionado à blockchain", bloco.index);
+            }
+            Ok(false) => {}
+            // Chegou antes por outro caminho (sincronização ou outro peer)
+            Err(ErroBloco::JaConhecido) => return,
+            Err(ErroBloco::AnteriorDesconhecido(_)) => {
+                self.sincronizar_em_segundo_plano(origem);
+                return;
+            }
+            // Sem punição nem marca de inválido: o peer só pode estar com o relógio adiantado
+            Err(e @ ErroBloco::NoFuturo { .. }) => {
+                println!("⏳ Bloco #{} ignorado por enquanto: {}", bloco.index, e);
+                return;
+            }
+            Err(ErroBloco::Invalido(e)) => {
+                println!("❌ Bloco #{} rejeitado: {}", bloco.index, e);
+                self.penalizar(origem, PONTOS_BLOCO_INVALIDO, &format!("bloco inválido: {}", e));
+                return;
+            }
+            Err(e) => {
+                println!("❌ Bloco #{} não aceito: {}", bloco.index, e);
+                return;
+            }
+        }
+
+        self.anunciar_bloco(&bloco);
+    }
+
+    /// Envia uma requisição pela conexão persistente e aguarda a resposta
+    async fn requisitar(&self, peer: &str, mensagem: Message) -> Result<Message, String> {
+        let (saida, respostas, resposta_pendente) = {
+            let peers = self.peers.lock().unwrap();
+            let p = peers.get(peer).ok_or_else(|| format!("peer {} não está conectado", peer))?;
+            (p.saida.clone(), Arc::clone(&p.respostas), Arc::clone(&p.resposta_pendente))
+        };
+
+        // Uma requisição por vez em cada peer; respostas atrasadas são descartadas
+        let mut respostas = respostas.lock().await;
+        while respostas.try_recv().is_ok() {}
+
+        let comando = mensagem.comando();
+        *resposta_pendente.lock().unwrap() = mensagem.comando_da_resposta();
+        let resposta = match saida.send(mensagem).await {
+            Ok(()) => time::timeout(TIMEOUT_RESPOSTA, respostas.recv()).await,
+            Err(_) => return Err(format!("peer {} desconectado", peer)),
+        };
+        *resposta_pendente.lock().unwrap() = None;
+        match resposta {
+            Ok(Some(resposta)) => Ok(resposta),
+            _ => Err(format!("peer {} não respondeu a {}", peer, comando)),
+        }
+    }
+
+    /// Baixa de um peer os blocos que faltam, em lotes, até alcançar o topo dele.
+    /// Cada lote é persistido ao ser conectado, então uma sincronização
+    /// interrompida recomeça do ponto em que parou. Com uma sincronização com o
+    /// mesmo peer já em andamento, não faz nada: as duas baixariam os mesmos lotes.
+    pub async fn sincronizar(&self, peer: &str) -> Result<usize, String> {
+        if !self.sincronizando.lock().unwrap().insert(peer.to_string()) {
+            return Ok(0);
+        }
+        let resultado = self.baixar_blocos(peer).await;
+        self.sincronizando.lock().unwrap().remove(peer);
+        resultado
+    }
+
+    /// Corpo de `sincronizar`: status, cabeçalhos e blocos em lotes
+    async fn baixar_blocos(&self, peer: &str) -> Result<usize, String> {
+        let status = match self.requisitar(peer, Message::GetStatus).await? {
+            Message::Status(s) => s,
+            outra => return Err(format!("resposta inesperada {}", outra.comando())),
+        };
+        {
+            let bc = self.blockchain.lock().unwrap();
+            if bc.conhece_bloco(&status.topo) {
+                return Ok(0);
+            }
+            println!(
+                "🔄 Sincronizando com {} (altura {} → {})",
+                peer,
+                bc.height(),
+                status.altura
+            );
+        }
+
+        let mut recebidos = 0;
+        loop {
+            let localizador = self.blockchain.lock().unwrap().localizador();
+            let resumos = match self.requisitar(peer, Message::GetHeaders(localizador)).await? {
+                Message::Headers(h) => h,
+                outra => return Err(format!("resposta inesperada {}", outra.comando())),
+            };
+
+            let faltando: Vec<String> = {
+                let bc = self.blockchain.lock().unwrap();
+                resumos
+                    .iter()
+                    .filter(|r| !bc.conhece_bloco(&r.hash))
+                    .map(|r| r.hash.clone())
+                    .collect()
+            };
+            if faltando.is_empty() {
+                break;
+            }
+
+            for lote in faltando.chunks(LOTE_BLOCOS) {
+                let blocos = match self.requisitar(peer, Message::GetBlocks(lote.to_vec())).await? {
+                    Message::Blocks(b) => b,
+                    outra => return Err(format!("resposta inesperada {}", outra.comando())),
+                };
+                if blocos.is_empty() {
+                    return Err("peer não entregou os blocos pedidos".to_string());
+                }
+
+                for bloco in blocos {
+                    let index = bloco.index;
+                    match conectar_bloco_em_segundo_plano(bloco, &self.blockchain, &self.mempool).await {
+                        Ok(_) => recebidos += 1,
+                        // Um anúncio trouxe o bloco enquanto o lote chegava
+                        Err(ErroBloco::JaConhecido) => {}
+                        Err(ErroBloco::Invalido(e)) => {
+                            self.penalizar(peer, PONTOS_BLOCO_INVALIDO, &format!("bloco inválido: {}", e));
+                            return Err(format!("bloco #{} rejeitado: {}", index, e));
+                        }
+                        Err(e) => return Err(format!("bloco #{} não aceito: {}", index, e)),
+                    }
+                }
+            }
+
+            if self.blockchain.lock().unwrap().conhece_bloco(&status.topo) {
+                break;
+            }
+        }
+
+        println!("✅ Sincronização com {} concluída: {} bloco(s) recebido(s)", peer, recebidos);
+
+        // Os outros peers podem estar tão atrasados quanto estávamos: o novo topo
+        // anunciado leva quem não o conhece a sincronizar conosco
+        if recebidos > 0 {
+            let topo = {
+                let bc = self.blockchain.lock().unwrap();
+                bc.bloco_por_hash(&bc.latest_hash())
+            };
+            if let Some(topo) = topo {
+                self.transmitir_exceto(&Message::Block(topo), peer);
+            }
+        }
+        Ok(recebidos)
+    }
+
+    /// Dispara a sincronização com o peer em uma tarefa separada
+    fn sincronizar_em_segundo_plano(&self, peer: &str) {
+        let servidor = self.clone();
+        let peer = peer.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = servidor.sincronizar(&peer).await {
+                println!("⚠️ Falha ao sincronizar com {}: {}", peer, e);
+            }
+        });
+    }
+
+    /// Sincroniza com todos os peers conectados
+    pub async fn sincronizar_com_peers(&self) {
+        for peer in self.get_peers() {
+            if let Err(e) = self.sincronizar(&peer).await {
+                println!("⚠️ Falha ao sincronizar com {}: {}", peer, e);
+            }
+        }
+    }
+
+    /// Indica se o endereço está banido (ver `chave_banimento`)
+    fn esta_banido(&self, endereco: &str) -> bool {
+        self.banidos
+            .lock()
+            .unwrap()
+            .esta_banido(&chave_banimento(endereco), Utc::now().timestamp())
+    }
+
+    /// Soma pontos de mau comportamento ao peer, banindo e desconectando
+    /// quando o total alcança `LIMITE_BANIMENTO`
+    fn penalizar(&self, endereco: &str, pontos: u32, motivo: &str) {
+        let (total, desconectar) = {
+            let peers = self.peers.lock().unwrap();
+            let peer = match peers.get(endereco) {
+                Some(p) => p,
+                None => return,
+            };
+            let total = peer.pontuacao.fetch_add(pontos, Ordering::Relaxed) + pontos;
+            (total, Arc::clone(&peer.desconectar))
+        };
+        println!("⚠️ Peer {} penalizado em {} ponto(s) ({}), total {}", endereco, pontos, motivo, total);
+
+        if total >= LIMITE_BANIMENTO {
+            self.banir(endereco, motivo);
+            desconectar.notify_one();
+        }
+    }
+
+    /// Bane o endereço (ver `chave_banimento`) e grava a lista de banidos
+    pub fn banir(&self, endereco: &str, motivo: &str) {
+        let ip = chave_banimento(endereco);
+        let mut banidos = self.banidos.lock().unwrap();
+        banidos.banir(&ip, motivo, Utc::now().timestamp());
+        println!("🚫 {} banido: {}", ip, motivo);
+        if let Err(e) = banidos.salvar_em_arquivo(&self.arquivo(ARQUIVO_BANIDOS)) {
+            println!("⚠️ Falha ao salvar banidos: {}", e);
+        }
+    }
+
+    /// Lista os IPs banidos e o tempo restante de cada banimento
+    pub fn listar_banidos(&self) {
+        let agora = Utc::now().timestamp();
+        let lista = self.banidos.lock().unwrap().listar(agora);
+        if lista.is_empty() {
+            println!("✅ Nenhum peer banido");
+            return;
+        }
+        println!("🚫 Peers banidos:");
+        for (ip, ban) in lista {
+            let restante = ban.expira_em - agora;
+            println!(
+                "- {} por mais {}h{:02}m: {}",
+                ip,
+                restante / 3600,
+                (restante % 3600) / 60,
+                ban.motivo
+            );
+        }
+    }
+
+    /// Enfileira uma mensagem para todos os peers conectados
+    fn transmitir(&self, mensagem: &Message) {
+        self.transmitir_exceto(mensagem, "");
+    }
+
+    /// Enfileira uma mensagem para todos os peers conectados, menos `excluido`.
+    /// Peers com a fila de saída cheia ficam sem o anúncio.
+    fn transmitir_exceto(&self, mensagem: &Message, excluido: &str) {
+        let peers = self.peers.lock().unwrap();
+        for (endereco, peer) in peers.iter().filter(|(e, _)| e.as_str() != excluido) {
+            if let Err(TrySendError::Full(_)) = peer.saida.try_send(mensagem.clone()) {
+                println!("⚠️ Fila de saída de {} cheia, {} descartada", endereco, mensagem.comando());
+            }
+        }
+    }
+
+    /// Conecta um bloco minerado localmente e, se ele entrou na cadeia ativa, o anuncia
+    pub fn submeter_bloco(&self, bloco: Block) -> Result<bool, String> {
+        let mudou = conectar_bloco(bloco.clone(), &self.blockchain, &self.mempool).map_err(|e| e.to_string())?;
+        if mudou {
+            self.anunciar_bloco(&bloco);
+        }
+        Ok(mudou)
+    }
+
+    /// Envia um bloco a todos os peers conectados
+    pub fn anunciar_bloco(&self, bloco: &Block) {
+        self.transmitir(&Message::Block(bloco.clone()));
+    }
+
+    /// Envia uma prova de vida a todos os peers conectados
+    pub fn anunciar_prova_de_vida(&self, prova: &ProvaDeVida) {
+        self.transmitir(&Message::ProvaDeVida(prova.clone()));
+        println!("📤 Prova de vida {} enviada para {} peer(s)", prova.id(), self.peers.lock().unwrap().len());
+    }
+
+    /// Envia uma transação a todos os peers conectados
+    pub fn anunciar_transacao(&self, tx: &Transaction) {
+        self.transmitir(&Message::Transaction(tx.clone()));
+        println!("📤 Transação {} enviada para {} peer(s)", tx.id, self.peers.lock().unwrap().len());
+    }
+
+    /// Lista os peers conectados
+    pub fn listar_peers(&self) {
+        let agora = Utc::now().timestamp();
+        println!("🔗 Peers conectados:");
+        for info in self.info_peers() {
+            let direcao = match info.direcao {
+                Direcao::Entrada => "entrada",
+                Direcao::Saida => "saída",
+            };
+            println!(
+                "- {} [{}] {} v{} altura {} pontuação {} (último contato há {}s)",
+                info.endereco,
+                direcao,
+                info.user_agent,
+                info.versao,
+                info.altura,
+                info.pontuacao,
+                agora - info.ultimo_contato
+            );
+        }
+        println!(
+            "📒 {} endereço(s) conhecido(s) | saída {}/{} | entrada {}/{}",
+            self.banco.lock().unwrap().len(),
+            self.conexoes(Direcao::Saida),
+            MAX_SAIDA,
+            self.conexoes(Direcao::Entrada),
+            MAX_ENTRADA
+        );
+    }
+}
+
+/// Valida e conecta um bloco, atualizando a mempool.
+/// Retorna `true` se a cadeia ativa mudou. Se ela mudou só até um bloco
+/// anterior (o novo ramo era inválido dali em diante), a mempool é atualizada
+/// e o erro é `Invalido`.
+pub(crate) fn conectar_bloco(bloco: Block, blockchain: &Arc<Mutex<Blockchain>>, mempool: &Arc<Mutex<Mempool>>) -> Result<bool, ErroBloco> {
+    let mut bc = blockchain.lock().unwrap();
+    let topo_anterior = bc.latest_hash();
+    let mudanca = bc.aceitar_bloco(bloco)?;
+
+    if mudanca.conectados.is_empty() {
+        return Ok(false);
+    }
+    // Numa reorganização ou reabsorção somem outputs que pendentes podem gastar
+    let reorganizou = mudanca.conectados[0].header.previous_hash != topo_anterior;
+    let reabsorveu = mudanca.conectados.iter().any(|b| !b.reclaimed.is_empty());
+
+    // A mempool é atualizada contra os UTXOs da cadeia, sem copiá-los (a ordem
+    // dos locks é blockchain antes de mempool)
+    let utxo_novo = bc.utxos();
+    let mut mempool = mempool.lock().unwrap();
+    mempool.remover_confirmadas(&mudanca.transacoes_confirmadas);
+    if reorganizou || reabsorveu {
+        mempool.revalidar_transacoes(utxo_novo);
+    }
+    mempool.revalidar_provas(utxo_novo);
+
+    // Transações e provas de vida de blocos desconectados voltam para a fila, se ainda válidas
+    for tx in mudanca.transacoes_orfas {
+        let txid = tx.id.clone();
+        if let Err(e) = mempool.adicionar(tx, utxo_novo) {
+            println!("🗑️ Transação órfã {} descartada: {}", txid, e);
+        }
+    }
+    for prova in mudanca.provas_orfas {
+        let id = prova.id();
+        if let Err(e) = mempool.adicionar_prova(prova, utxo_novo) {
+            println!("🗑️ Prova de vida órfã {} descartada: {}", id, e);
+        }
+    }
+
+    if let Some(motivo) = mudanca.invalido {
+        return Err(ErroBloco::Invalido(motivo));
+    }
+
+    Ok(true)
+}
+
+/// `conectar_bloco` num thread de bloqueio: validar e gravar o bloco segura o
+/// lock da blockchain por tempo demais para um thread do runtime
+async fn conectar_bloco_em_segundo_plano(
+    bloco: Block,
+    blockchain: &Arc<Mutex<Blockchain>>,
+    mempool: &Arc<Mutex<Mempool>>,
+) -> Result<bool, ErroBloco> {
+    let (blockchain, mempool) = (Arc::clone(blockchain), Arc::clone(mempool));
+    tokio::task::spawn_blocking(move || conectar_bloco(bloco, &blockchain, &mempool))
+        .await
+        .expect("tarefa de conexão de bloco interrompida")
+}
+
+/// Tarefa de escrita de uma conexão: esvazia a fila de saída no socket
+/// até a fila ser fechada ou a escrita falhar
+async fn escrever_mensagens(mut escrita: OwnedWriteHalf, mut fila: mpsc::Receiver<Message>, desconectar: Arc<Notify>) {
+    while let Some(mensagem) = fila.recv().await {
+        let resultado = time::timeout(TIMEOUT_ESCRITA, escrever_mensagem_async(&mut escrita, &mensagem)).await;
+        if !matches!(resultado, Ok(Ok(()))) {
+            println!("⚠️ Falha ao enviar {}, fechando a conexão", mensagem.comando());
+            desconectar.notify_one();
+            break;
+        }
+    }
+    let _ = escrita.shutdown().await;
+}
+
+/// IP de um endereço "ip:porta"
+fn ip_de(endereco: &str) -> String {
+    endereco
+        .parse::<SocketAddr>()
+        .map(|a| a.ip().to_string())
+        .unwrap_or_else(|_| endereco.to_string())
+}
+
+/// O que fica banido por mau comportamento de `endereco`: o IP, valendo para
+/// todas as portas, menos em loopback, onde cada porta é um nó diferente (como
+/// na rede local de testes)
+fn chave_banimento(endereco: &str) -> String {
+    match endereco.parse::<SocketAddr>() {
+        Ok(a) if a.ip().is_loopback() => a.to_string(),
+        Ok(a) => a.ip().to_string(),
+        Err(_) => endereco.to_string(),
+    }
+}
@@ -0,0 +1,218 @@
+// src/peerdb.rs
+
+use serde::{Serialize, Deserialize};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Write};
+
+/// Arquivo onde o nó guarda os endereços de peers conhecidos (ao lado do armazém de blocos)
+pub const ARQUIVO_PEERS: &str = "peers.json";
+
+/// Limite de endereços guardados
+const MAX_ENDERECOS: usize = 2000;
+
+/// Tentativas de conexão falhas seguidas antes de esquecer o endereço
+const MAX_FALHAS: u32 = 3;
+
+/// Endereço anunciado na rede via ADDR
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnderecoPeer {
+    pub endereco: String,
+    pub visto_em: i64, // última vez em que alguém falou com esse peer
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RegistroPeer {
+    visto_em: i64,
+    falhas: u32,
+}
+
+/// Endereços de peers aprendidos por configuração ou por gossip
+#[derive(Debug, Clone, Default)]
+pub struct BancoPeers {
+    enderecos: HashMap<String, RegistroPeer>,
+    proprios: HashSet<String>, // endereços que levaram a nós mesmos
+}
+
+impl BancoPeers {
+    pub fn new() -> Self {
+        BancoPeers::default()
+    }
+
+    pub fn salvar_em_arquivo(&self, caminho: &str) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.enderecos)?;
+        let mut file = File::create(caminho)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn carregar_de_arquivo(caminho: &str) -> Option<BancoPeers> {
+        let mut contents = String::new();
+        File::open(caminho).ok()?.read_to_string(&mut contents).ok()?;
+        let enderecos = serde_json::from_str(&contents).ok()?;
+        Some(BancoPeers { enderecos, proprios: HashSet::new() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.enderecos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.enderecos.is_empty()
+    }
+
+    /// Registra um endereço; devolve `true` se ele ainda não era conhecido
+    pub fn adicionar(&mut self, endereco: &str, visto_em: i64) -> bool {
+        if self.proprios.contains(endereco) {
+            return false;
+        }
+        if let Some(registro) = self.enderecos.get_mut(endereco) {
+            registro.visto_em = registro.visto_em.max(visto_em);
+            return false;
+        }
+        if self.enderecos.len() >= MAX_ENDERECOS {
+            self.esquecer_mais_antigo();
+        }
+        self.enderecos
+            .insert(endereco.to_string(), RegistroPeer { visto_em, falhas: 0 });
+        true
+    }
+
+    /// Conexão bem-sucedida: zera as falhas e atualiza o último contato
+    pub fn marcar_sucesso(&mut self, endereco: &str) {
+        let registro = self.enderecos.entry(endereco.to_string()).or_default();
+        registro.visto_em = Utc::now().timestamp();
+        registro.falhas = 0;
+    }
+
+    /// Conta uma tentativa falha, esquecendo o endereço após várias seguidas
+    pub fn marcar_falha(&mut self, endereco: &str) {
+        if let Some(registro) = self.enderecos.get_mut(endereco) {
+            registro.falhas += 1;
+            if registro.falhas >= MAX_FALHAS {
+                self.enderecos.remove(endereco);
+            }
+        }
+    }
+
+    /// Esquece um endereço que aponta para o próprio nó e não volta a aceitá-lo
+    pub fn marcar_proprio(&mut self, endereco: &str) {
+        self.enderecos.remove(endereco);
+        self.proprios.insert(endereco.to_string());
+    }
+
+    /// Endereços para tentar conexão, dos vistos mais recentemente para os mais antigos
+    pub fn candidatos(&self, ignorar: &[String], limite: usize) -> Vec<String> {
+        let mut lista: Vec<(&String, &RegistroPeer)> = self
+            .enderecos
+            .iter()
+            .filter(|(e, _)| !ignorar.contains(e))
+            .collect();
+        lista.sort_by(|a, b| a.1.falhas.cmp(&b.1.falhas).then(b.1.visto_em.cmp(&a.1.visto_em)));
+        lista.into_iter().take(limite).map(|(e, _)| e.clone()).collect()
+    }
+
+    /// Endereços a anunciar em resposta a GETADDR
+    pub fn para_anunciar(&self, limite: usize) -> Vec<EnderecoPeer> {
+        let mut lista: Vec<EnderecoPeer> = self
+            .enderecos
+            .iter()
+            .filter(|(_, r)| r.falhas == 0)
+            .map(|(e, r)| EnderecoPeer { endereco: e.clone(), visto_em: r.visto_em })
+            .collect();
+        lista.sort_by_key(|a| std::cmp::Reverse(a.visto_em));
+        lista.truncate(limite);
+        lista
+    }
+
+    fn esquecer_mais_antigo(&mut self) {
+        let antigo = self
+            .enderecos
+            .iter()
+            .min_by_key(|(_, r)| r.visto_em)
+            .map(|(e, _)| e.clone());
+        if let Some(e) = antigo {
+            self.enderecos.remove(&e);
+        }
+    }
+}
+
+/// Arquivo com os banimentos em vigor
+pub const ARQUIVO_BANIDOS: &str = "banidos.json";
+
+/// Duração de um banimento por mau comportamento (24 horas)
+pub const DURACAO_BANIMENTO: i64 = 24 * 60 * 60;
+
+/// Motivo e prazo de um banimento
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Banimento {
+    pub motivo: String,
+    pub banido_em: i64,
+    pub expira_em: i64,
+}
+
+/// IPs banidos temporariamente por mau comportamento
+#[derive(Debug, Clone, Default)]
+pub struct ListaBanidos {
+    banidos: HashMap<String, Banimento>, // ip → banimento
+}
+
+impl ListaBanidos {
+    pub fn new() -> Self {
+        ListaBanidos::default()
+    }
+
+    pub fn salvar_em_arquivo(&self, caminho: &str) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.banidos)?;
+        let mut file = File::create(caminho)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn carregar_de_arquivo(caminho: &str) -> Option<ListaBanidos> {
+        let mut contents = String::new();
+        File::open(caminho).ok()?.read_to_string(&mut contents).ok()?;
+        let banidos = serde_json::from_str(&contents).ok()?;
+        Some(ListaBanidos { banidos })
+    }
+
+    /// Bane o IP por `DURACAO_BANIMENTO` a partir de `agora`
+    pub fn banir(&mut self, ip: &str, motivo: &str, agora: i64) {
+        self.banidos.insert(
+            ip.to_string(),
+            Banimento {
+                motivo: motivo.to_string(),
+                banido_em: agora,
+                expira_em: agora + DURACAO_BANIMENTO,
+            },
+        );
+    }
+
+    pub fn desbanir(&mut self, ip: &str) -> bool {
+        self.banidos.remove(ip).is_some()
+    }
+
+    pub fn esta_banido(&self, ip: &str, agora: i64) -> bool {
+        self.banidos.get(ip).is_some_and(|b| b.expira_em > agora)
+    }
+
+    /// Descarta banimentos vencidos; devolve quantos foram removidos
+    pub fn remover_expirados(&mut self, agora: i64) -> usize {
+        let antes = self.banidos.len();
+        self.banidos.retain(|_, b| b.expira_em > agora);
+        antes - self.banidos.len()
+    }
+
+    /// Banimentos em vigor, dos que expiram primeiro para os últimos
+    pub fn listar(&self, agora: i64) -> Vec<(String, Banimento)> {
+        let mut lista: Vec<(String, Banimento)> = self
+            .banidos
+            .iter()
+            .filter(|(_, b)| b.expira_em > agora)
+            .map(|(ip, b)| (ip.clone(), b.clone()))
+            .collect();
+        lista.sort_by_key(|(_, b)| b.expira_em);
+        lista
+    }
+}
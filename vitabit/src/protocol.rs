@@ -0,0 +1,411 @@
+// src/protocol.rs
+
+use crate::block::Block;
+use crate::peerdb::EnderecoPeer;
+use crate::transaction::{ProvaDeVida, Transaction};
+use crate::codificacao::{codificar, decodificar};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::fmt;
+use std::io::{self, Read, Write};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Bytes mágicos que abrem toda mensagem da rede VitaBit
+pub const MAGIC: [u8; 4] = *b"VBIT";
+
+/// Versão do envelope das mensagens
+pub const VERSAO_PROTOCOLO: u8 = 2;
+
+/// Versão do protocolo de rede anunciada no VERSION (2: VERSION leva a hora do nó)
+pub const VERSAO_NO: u32 = 2;
+
+/// Menor versão de peer com a qual ainda conversamos
+pub const VERSAO_NO_MINIMA: u32 = 2;
+
+/// Identificação do software enviada no handshake
+pub const USER_AGENT: &str = concat!("/vitabit:", env!("CARGO_PKG_VERSION"), "/");
+
+/// Tamanho máximo do payload de uma mensagem
+pub const MAX_PAYLOAD: u32 = 32 * 1024 * 1024;
+
+const TAMANHO_COMANDO: usize = 12;
+
+/// magic (4) + versão (1) + comando (12) + tamanho (4) + checksum (4)
+pub const TAMANHO_CABECALHO: usize = 4 + 1 + TAMANHO_COMANDO + 4 + 4;
+
+/// Apresentação enviada por cada lado ao abrir uma conexão
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersaoNo {
+    pub versao: u32,
+    pub altura: u64,
+    pub topo: String,
+    pub user_agent: String,
+    pub porta_escuta: u16, // porta em que o peer aceita conexões
+    pub nonce: u64,        // detecta conexões consigo mesmo
+    pub timestamp: i64,    // relógio local de quem envia, para a hora ajustada pela rede
+}
+
+/// Estado atual da cadeia, devolvido em resposta a GET_STATUS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusNo {
+    pub altura: u64,
+    pub topo: String,
+}
+
+/// Resumo de um bloco enviado em resposta a GET_HEADERS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumoBloco {
+    pub index: u64,
+    pub hash: String,
+    pub previous_hash: String,
+}
+
+/// Mensagens trocadas entre os nós
+#[derive(Debug, Clone)]
+pub enum Message {
+    Version(VersaoNo),
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    GetAddr,
+    Addr(Vec<EnderecoPeer>),
+    Transaction(Transaction),
+    Block(Block),
+    GetStatus,
+    Status(StatusNo),
+    GetHeaders(Vec<String>), // localizador de blocos
+    Headers(Vec<ResumoBloco>),
+    GetBlocks(Vec<String>),  // hashes pedidos
+    Blocks(Vec<Block>),
+    ProvaDeVida(ProvaDeVida),
+}
+
+/// Erros ao ler ou decodificar mensagens
+#[derive(Debug)]
+pub enum ErroProtocolo {
+    MagicInvalido([u8; 4]),
+    VersaoNaoSuportada(u8),
+    ComandoDesconhecido(String),
+    PayloadGrande(u32),
+    ChecksumInvalido,
+    PayloadInvalido(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for ErroProtocolo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErroProtocolo::MagicInvalido(m) => write!(f, "magic inválido {:02x?}", m),
+            ErroProtocolo::VersaoNaoSuportada(v) => write!(f, "versão de protocolo {} não suportada", v),
+            ErroProtocolo::ComandoDesconhecido(c) => write!(f, "comando desconhecido '{}'", c),
+            ErroProtocolo::PayloadGrande(t) => write!(f, "payload de {} bytes excede o limite de {}", t, MAX_PAYLOAD),
+            ErroProtocolo::ChecksumInvalido => write!(f, "checksum do payload não confere"),
+            ErroProtocolo::PayloadInvalido(e) => write!(f, "payload inválido: {}", e),
+            ErroProtocolo::Io(e) => write!(f, "erro de E/S: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ErroProtocolo {
+    fn from(e: io::Error) -> Self {
+        ErroProtocolo::Io(e)
+    }
+}
+
+impl Message {
+    /// Nome do comando gravado no envelope
+    pub fn comando(&self) -> &'static str {
+        match self {
+            Message::Version(_) => "VERSION",
+            Message::Verack => "VERACK",
+            Message::Ping(_) => "PING",
+            Message::Pong(_) => "PONG",
+            Message::GetAddr => "GET_ADDR",
+            Message::Addr(_) => "ADDR",
+            Message::Transaction(_) => "TRANSACTION",
+            Message::Block(_) => "BLOCK",
+            Message::GetStatus => "GET_STATUS",
+            Message::Status(_) => "STATUS",
+            Message::GetHeaders(_) => "GET_HEADERS",
+            Message::Headers(_) => "HEADERS",
+            Message::GetBlocks(_) => "GET_BLOCKS",
+            Message::Blocks(_) => "BLOCKS",
+            Message::ProvaDeVida(_) => "PROVA_VIDA",
+        }
+    }
+
+    /// Comando com que o peer responde a esta mensagem, se ela for uma requisição
+    pub fn comando_da_resposta(&self) -> Option<&'static str> {
+        match self {
+            Message::GetStatus => Some("STATUS"),
+            Message::GetHeaders(_) => Some("HEADERS"),
+            Message::GetBlocks(_) => Some("BLOCKS"),
+            _ => None,
+        }
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        match self {
+            Message::Version(v) => codificar(v),
+            Message::Verack | Message::GetStatus | Message::GetAddr => Vec::new(),
+            Message::Addr(a) => codificar(a),
+            Message::Ping(n) | Message::Pong(n) => codificar(n),
+            Message::Transaction(tx) => codificar(tx),
+            Message::Block(b) => codificar(b),
+            Message::Status(s) => codificar(s),
+            Message::GetHeaders(l) => codificar(l),
+            Message::Headers(h) => codificar(h),
+            Message::GetBlocks(h) => codificar(h),
+            Message::Blocks(b) => codificar(b),
+            Message::ProvaDeVida(p) => codificar(p),
+        }
+    }
+
+    fn from_payload(comando: &str, payload: &[u8]) -> Result<Self, ErroProtocolo> {
+        let invalido = ErroProtocolo::PayloadInvalido;
+        Ok(match comando {
+            "VERSION" => Message::Version(decodificar(payload).map_err(invalido)?),
+            "VERACK" => Message::Verack,
+            "PING" => Message::Ping(decodificar(payload).map_err(invalido)?),
+            "PONG" => Message::Pong(decodificar(payload).map_err(invalido)?),
+            "GET_STATUS" => Message::GetStatus,
+            "GET_ADDR" => Message::GetAddr,
+            "ADDR" => Message::Addr(decodificar(payload).map_err(invalido)?),
+            "TRANSACTION" => Message::Transaction(decodificar(payload).map_err(invalido)?),
+            "BLOCK" => Message::Block(decodificar(payload).map_err(invalido)?),
+            "STATUS" => Message::Status(decodificar(payload).map_err(invalido)?),
+            "GET_HEADERS" => Message::GetHeaders(decodificar(payload).map_err(invalido)?),
+            "HEADERS" => Message::Headers(decodificar(payload).map_err(invalido)?),
+            "GET_BLOCKS" => Message::GetBlocks(decodificar(payload).map_err(invalido)?),
+            "BLOCKS" => Message::Blocks(decodificar(payload).map_err(invalido)?),
+            "PROVA_VIDA" => Message::ProvaDeVida(decodificar(payload).map_err(invalido)?),
+            outro => return Err(ErroProtocolo::ComandoDesconhecido(outro.to_string())),
+        })
+    }
+
+    /// Serializa a mensagem com o envelope completo
+    pub fn codificar(&self) -> Result<Vec<u8>, ErroProtocolo> {
+        let payload = self.payload();
+        if payload.len() > MAX_PAYLOAD as usize {
+            return Err(ErroProtocolo::PayloadGrande(payload.len() as u32));
+        }
+
+        debug_assert!(self.comando().len() <= TAMANHO_COMANDO, "comando {} não cabe no envelope", self.comando());
+        let mut comando = [0u8; TAMANHO_COMANDO];
+        comando[..self.comando().len()].copy_from_slice(self.comando().as_bytes());
+
+        let mut bytes = Vec::with_capacity(TAMANHO_CABECALHO + payload.len());
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSAO_PROTOCOLO);
+        bytes.extend_from_slice(&comando);
+        bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&checksum(&payload));
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
+    }
+}
+
+/// Primeiros 4 bytes do SHA256 duplo do payload
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(Sha256::digest(payload));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Remonta mensagens a partir de bytes que chegam em pedaços arbitrários
+#[derive(Debug, Default)]
+pub struct Decodificador {
+    buffer: Vec<u8>,
+}
+
+impl Decodificador {
+    pub fn new() -> Self {
+        Decodificador { buffer: Vec::new() }
+    }
+
+    /// Acrescenta bytes lidos do socket
+    pub fn alimentar(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Extrai a próxima mensagem completa, se já houver bytes suficientes
+    pub fn proxima(&mut self) -> Result<Option<Message>, ErroProtocolo> {
+        if self.buffer.len() < TAMANHO_CABECALHO {
+            return Ok(None);
+        }
+
+        let magic: [u8; 4] = self.buffer[0..4].try_into().unwrap();
+        if magic != MAGIC {
+            return Err(ErroProtocolo::MagicInvalido(magic));
+        }
+
+        let versao = self.buffer[4];
+        if versao != VERSAO_PROTOCOLO {
+            return Err(ErroProtocolo::VersaoNaoSuportada(versao));
+        }
+
+        let comando_bytes = &self.buffer[5..5 + TAMANHO_COMANDO];
+        let fim = comando_bytes.iter().position(|b| *b == 0).unwrap_or(TAMANHO_COMANDO);
+        let comando = String::from_utf8_lossy(&comando_bytes[..fim]).to_string();
+
+        let pos = 5 + TAMANHO_COMANDO;
+        let tamanho = u32::from_le_bytes(self.buffer[pos..pos + 4].try_into().unwrap());
+        if tamanho > MAX_PAYLOAD {
+            return Err(ErroProtocolo::PayloadGrande(tamanho));
+        }
+
+        let total = TAMANHO_CABECALHO + tamanho as usize;
+        if self.buffer.len() < total {
+            return Ok(None);
+        }
+
+        let esperado: [u8; 4] = self.buffer[pos + 4..pos + 8].try_into().unwrap();
+        let payload: Vec<u8> = self.buffer.drain(..total).skip(TAMANHO_CABECALHO).collect();
+        if checksum(&payload) != esperado {
+            return Err(ErroProtocolo::ChecksumInvalido);
+        }
+
+        Message::from_payload(&comando, &payload).map(Some)
+    }
+}
+
+/// Lê do stream até completar uma mensagem. Retorna `None` quando o
+/// peer fecha a conexão sem deixar mensagem pela metade.
+pub fn ler_mensagem<R: Read>(stream: &mut R, decodificador: &mut Decodificador) -> Result<Option<Message>, ErroProtocolo> {
+    let mut pedaco = [0u8; 8192];
+    loop {
+        if let Some(msg) = decodificador.proxima()? {
+            return Ok(Some(msg));
+        }
+
+        let n = stream.read(&mut pedaco)?;
+        if n == 0 {
+            if decodificador.buffer.is_empty() {
+                return Ok(None);
+            }
+            return Err(ErroProtocolo::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        decodificador.alimentar(&pedaco[..n]);
+    }
+}
+
+/// Codifica e escreve uma mensagem no stream
+pub fn escrever_mensagem<W: Write>(stream: &mut W, msg: &Message) -> Result<(), ErroProtocolo> {
+    stream.write_all(&msg.codificar()?)?;
+    Ok(())
+}
+
+/// Versão assíncrona de `ler_mensagem`, para os sockets do tokio
+pub async fn ler_mensagem_async<R: AsyncRead + Unpin>(stream: &mut R, decodificador: &mut Decodificador) -> Result<Option<Message>, ErroProtocolo> {
+    let mut pedaco = [0u8; 8192];
+    loop {
+        if let Some(msg) = decodificador.proxima()? {
+            return Ok(Some(msg));
+        }
+
+        let n = stream.read(&mut pedaco).await?;
+        if n == 0 {
+            if decodificador.buffer.is_empty() {
+                return Ok(None);
+            }
+            return Err(ErroProtocolo::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        decodificador.alimentar(&pedaco[..n]);
+    }
+}
+
+/// Versão assíncrona de `escrever_mensagem`
+pub async fn escrever_mensagem_async<W: AsyncWrite + Unpin>(stream: &mut W, msg: &Message) -> Result<(), ErroProtocolo> {
+    stream.write_all(&msg.codificar()?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::OutPoint;
+    use secp256k1::SecretKey;
+
+    fn todas_as_mensagens() -> Vec<Message> {
+        let genesis = Block::genesis();
+        let chave = SecretKey::from_slice(&[7; 32]).unwrap();
+        vec![
+            Message::Version(VersaoNo {
+                versao: VERSAO_NO,
+                altura: 3,
+                topo: genesis.hash.clone(),
+                user_agent: USER_AGENT.to_string(),
+                porta_escuta: 6000,
+                nonce: 42,
+                timestamp: 1_700_000_000,
+            }),
+            Message::Verack,
+            Message::Ping(1),
+            Message::Pong(2),
+            Message::GetAddr,
+            Message::Addr(vec![EnderecoPeer { endereco: "127.0.0.1:6000".to_string(), visto_em: 5 }]),
+            Message::Transaction(genesis.transactions[0].clone()),
+            Message::Block(genesis.clone()),
+            Message::GetStatus,
+            Message::Status(StatusNo { altura: 0, topo: genesis.hash.clone() }),
+            Message::GetHeaders(vec![genesis.hash.clone()]),
+            Message::Headers(vec![ResumoBloco {
+                index: 0,
+                hash: genesis.hash.clone(),
+                previous_hash: genesis.header.previous_hash.clone(),
+            }]),
+            Message::GetBlocks(vec![genesis.hash.clone()]),
+            Message::Blocks(vec![genesis.clone()]),
+            Message::ProvaDeVida(ProvaDeVida::new(vec![OutPoint::new(&genesis.transactions[0].id, 0)], 10, &chave)),
+        ]
+    }
+
+    #[test]
+    fn toda_mensagem_volta_igual_do_envelope() {
+        let mensagens = todas_as_mensagens();
+        let mut bytes = Vec::new();
+        for msg in &mensagens {
+            assert!(msg.comando().len() <= TAMANHO_COMANDO, "comando {} longo demais", msg.comando());
+            bytes.extend(msg.codificar().unwrap());
+        }
+
+        // Tudo de uma vez, em pedaços pequenos: o decodificador remonta cada mensagem
+        let mut decodificador = Decodificador::new();
+        let mut lidas = Vec::new();
+        for pedaco in bytes.chunks(7) {
+            decodificador.alimentar(pedaco);
+            while let Some(msg) = decodificador.proxima().unwrap() {
+                lidas.push(msg);
+            }
+        }
+
+        assert_eq!(lidas.len(), mensagens.len());
+        for (original, lida) in mensagens.iter().zip(&lidas) {
+            assert_eq!(lida.comando(), original.comando());
+            assert_eq!(lida.codificar().unwrap(), original.codificar().unwrap());
+        }
+    }
+
+    #[test]
+    fn recusa_envelope_adulterado() {
+        let bytes = Message::Ping(9).codificar().unwrap();
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        let mut decodificador = Decodificador::new();
+        decodificador.alimentar(&magic);
+        assert!(matches!(decodificador.proxima(), Err(ErroProtocolo::MagicInvalido(_))));
+
+        let mut payload = bytes.clone();
+        *payload.last_mut().unwrap() ^= 1;
+        let mut decodificador = Decodificador::new();
+        decodificador.alimentar(&payload);
+        assert!(matches!(decodificador.proxima(), Err(ErroProtocolo::ChecksumInvalido)));
+
+        let mut comando = bytes;
+        comando[5..9].copy_from_slice(b"PONX");
+        let mut decodificador = Decodificador::new();
+        decodificador.alimentar(&comando);
+        assert!(matches!(decodificador.proxima(), Err(ErroProtocolo::ComandoDesconhecido(_))));
+    }
+}
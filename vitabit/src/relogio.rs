@@ -0,0 +1,205 @@
+// src/relogio.rs
+
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Maior desvio da rede aplicado ao relógio local (70 minutos, como no Bitcoin)
+pub const MAX_DESVIO_REDE: i64 = 70 * 60;
+
+/// Mínimo de peers informando a hora para o desvio da rede valer
+const MIN_AMOSTRAS: usize = 5;
+
+/// Máximo de peers guardados no cálculo do desvio
+const MAX_AMOSTRAS: usize = 200;
+
+/// Fonte da hora atual, em segundos Unix; trocável nos testes
+pub trait Relogio: Send + Sync {
+    fn agora(&self) -> i64;
+}
+
+/// Relógio do sistema
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RelogioSistema;
+
+impl Relogio for RelogioSistema {
+    fn agora(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
+
+/// Relógio que só anda quando mandado, para testes e simulações
+#[derive(Debug, Default)]
+pub struct RelogioManual(AtomicI64);
+
+impl RelogioManual {
+    pub fn new(agora: i64) -> Self {
+        RelogioManual(AtomicI64::new(agora))
+    }
+
+    pub fn definir(&self, agora: i64) {
+        self.0.store(agora, Ordering::Relaxed);
+    }
+
+    pub fn avancar(&self, segundos: i64) {
+        self.0.fetch_add(segundos, Ordering::Relaxed);
+    }
+}
+
+impl Relogio for RelogioManual {
+    fn agora(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Hora ajustada pela rede: o relógio local mais a mediana dos desvios
+/// informados pelos peers no handshake (um por IP)
+pub struct TempoRede {
+    relogio: Arc<dyn Relogio>,
+    desvios: Mutex<HashMap<String, i64>>,
+}
+
+impl TempoRede {
+    /// Hora da rede a partir do relógio do sistema
+    pub fn new() -> Self {
+        Self::com_relogio(Arc::new(RelogioSistema))
+    }
+
+    /// Hora da rede a partir de outro relógio local
+    pub fn com_relogio(relogio: Arc<dyn Relogio>) -> Self {
+        TempoRede {
+            relogio,
+            desvios: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Hora do relógio local, sem o desvio da rede
+    pub fn local(&self) -> i64 {
+        self.relogio.agora()
+    }
+
+    /// Registra a hora que o peer `ip` informou; só a primeira amostra de cada IP conta
+    pub fn registrar(&self, ip: &str, hora_do_peer: i64) {
+        let mut desvios = self.desvios.lock().unwrap();
+        if desvios.len() < MAX_AMOSTRAS && !desvios.contains_key(ip) {
+            desvios.insert(ip.to_string(), hora_do_peer - self.local());
+        }
+    }
+
+    /// Mediana dos desvios dos peers; zero com poucas amostras ou se a mediana
+    /// passa de MAX_DESVIO_REDE (aí o relógio local é que deve estar certo)
+    pub fn desvio(&self) -> i64 {
+        let mut desvios: Vec<i64> = self.desvios.lock().unwrap().values().copied().collect();
+        if desvios.len() < MIN_AMOSTRAS {
+            return 0;
+        }
+        desvios.sort_unstable();
+        let mediana = desvios[desvios.len() / 2];
+        if mediana.abs() > MAX_DESVIO_REDE {
+            0
+        } else {
+            mediana
+        }
+    }
+}
+
+impl Relogio for TempoRede {
+    fn agora(&self) -> i64 {
+        self.local() + self.desvio()
+    }
+}
+
+impl Default for TempoRede {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for TempoRede {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TempoRede")
+            .field("local", &self.local())
+            .field("desvio", &self.desvio())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tempo_em(agora: i64) -> (Arc<RelogioManual>, TempoRede) {
+        let relogio = Arc::new(RelogioManual::new(agora));
+        let tempo = TempoRede::com_relogio(relogio.clone());
+        (relogio, tempo)
+    }
+
+    #[test]
+    fn relogio_manual_so_anda_quando_mandado() {
+        let relogio = RelogioManual::new(100);
+        assert_eq!(relogio.agora(), 100);
+        relogio.avancar(5);
+        assert_eq!(relogio.agora(), 105);
+        relogio.definir(10);
+        assert_eq!(relogio.agora(), 10);
+    }
+
+    #[test]
+    fn sem_amostras_suficientes_vale_o_relogio_local() {
+        let (_, tempo) = tempo_em(1_000);
+        for i in 0..MIN_AMOSTRAS - 1 {
+            tempo.registrar(&format!("10.0.0.{}", i), 1_600);
+        }
+        assert_eq!(tempo.desvio(), 0);
+        assert_eq!(tempo.agora(), 1_000);
+    }
+
+    #[test]
+    fn aplica_a_mediana_dos_desvios() {
+        let (relogio, tempo) = tempo_em(1_000);
+        for (i, desvio) in [-300, 10, 60, 120, 3_000].iter().enumerate() {
+            tempo.registrar(&format!("10.0.0.{}", i), 1_000 + desvio);
+        }
+        assert_eq!(tempo.desvio(), 60);
+        assert_eq!(tempo.agora(), 1_060);
+
+        // O desvio é guardado, não a hora: acompanha o relógio local
+        relogio.avancar(40);
+        assert_eq!(tempo.agora(), 1_100);
+        assert_eq!(tempo.local(), 1_040);
+    }
+
+    #[test]
+    fn conta_uma_amostra_por_ip() {
+        let (_, tempo) = tempo_em(1_000);
+        for _ in 0..MIN_AMOSTRAS {
+            tempo.registrar("10.0.0.1", 1_600);
+        }
+        assert_eq!(tempo.desvio(), 0);
+
+        // A primeira amostra do IP fica; as seguintes são ignoradas
+        tempo.registrar("10.0.0.1", 9_999);
+        for i in 2..=MIN_AMOSTRAS {
+            tempo.registrar(&format!("10.0.0.{}", i), 1_600);
+        }
+        assert_eq!(tempo.desvio(), 600);
+    }
+
+    #[test]
+    fn ignora_mediana_acima_do_limite() {
+        let (_, tempo) = tempo_em(1_000);
+        for i in 0..MIN_AMOSTRAS {
+            tempo.registrar(&format!("10.0.0.{}", i), 1_000 + MAX_DESVIO_REDE);
+        }
+        assert_eq!(tempo.desvio(), MAX_DESVIO_REDE);
+
+        let (_, tempo) = tempo_em(1_000);
+        for i in 0..MIN_AMOSTRAS {
+            tempo.registrar(&format!("10.0.0.{}", i), 1_000 - MAX_DESVIO_REDE - 1);
+        }
+        assert_eq!(tempo.desvio(), 0);
+        assert_eq!(tempo.agora(), 1_000);
+    }
+}
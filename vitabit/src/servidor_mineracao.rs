@@ -0,0 +1,370 @@
+// src/servidor_mineracao.rs
+
+use crate::alvo::Alvo;
+use crate::block::Block;
+use crate::codificacao::codificar;
+use crate::miner::{aplicar_extra_nonce, Minerador};
+use crate::transaction::{OutPoint, ProvaDeVida, Transaction, TxOutput};
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+/// Porta local usada quando nenhuma é configurada
+pub const PORTA_MINERACAO: u16 = 6020;
+
+/// Modelos entregues que continuam aceitando solução
+const MAX_MODELOS: u64 = 256;
+
+/// Maior linha de requisição aceita
+const MAX_REQUISICAO: u64 = 4096;
+
+/// Trabalho entregue a um minerador externo: campos do cabeçalho, alvo,
+/// saídas da coinbase e transações escolhidas da mempool. Basta variar os 8
+/// bytes finais de `cabecalho` (o nonce, little-endian) até o SHA256 atingir `alvo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModeloBloco {
+    pub id: String,                   // Identifica o modelo na submissão
+    pub altura: u64,
+    pub version: u32,
+    pub previous_hash: String,
+    pub merkle_root: String,
+    pub reclaimed_hash: String,
+    pub keepalive_hash: String,
+    pub extra_reward: u64,
+    pub timestamp: i64,
+    pub bits: u32,
+    pub alvo: String,                 // Alvo em hex big-endian, comparável ao hash
+    pub cabecalho: String,            // Cabeçalho codificado em hex, com nonce zero
+    pub coinbase: Vec<TxOutput>,      // Saídas da coinbase (emissão + taxas)
+    pub transacoes: Vec<Transaction>, // Transações da mempool, sem a coinbase
+    pub reabsorvidos: Vec<OutPoint>,  // Outputs reabsorvidos pela regra dos 100 anos
+    pub provas_de_vida: Vec<ProvaDeVida>,
+}
+
+/// Requisição de um minerador externo, uma por linha em JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "metodo", rename_all = "snake_case")]
+pub enum Requisicao {
+    /// Pede um modelo novo; sem endereço, a recompensa vai para o do nó
+    Modelo {
+        #[serde(default)]
+        endereco: Option<String>,
+    },
+    /// Entrega o nonce que resolve o modelo `id`
+    Submeter { id: String, nonce: u64 },
+}
+
+/// Resposta do servidor, uma por linha em JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "resultado", rename_all = "snake_case")]
+pub enum Resposta {
+    Modelo(Box<ModeloBloco>),
+    Aceito { hash: String, altura: u64 },
+    Recusado { erro: String },
+}
+
+/// Servidor getwork para mineradores externos (ou um pool) na mesma máquina:
+/// entrega modelos de bloco e recebe as soluções em JSON sobre TCP, só em 127.0.0.1
+#[derive(Clone)]
+pub struct ServidorMineracao {
+    minerador: Minerador,
+    porta: u16,
+    endereco_padrao: String,
+    modelos: Arc<Mutex<HashMap<u64, Block>>>, // Modelos entregues, pelo extra-nonce
+    proximo_extra: Arc<AtomicU64>,
+    desligar: Arc<watch::Sender<bool>>,
+}
+
+impl ServidorMineracao {
+    /// Servidor que monta os modelos com `minerador` e, sem endereço na
+    /// requisição, paga a recompensa a `endereco_padrao`
+    pub fn new(minerador: Minerador, porta: u16, endereco_padrao: &str) -> Self {
+        let (desligar, _) = watch::channel(false);
+        ServidorMineracao {
+            minerador,
+            porta,
+            endereco_padrao: endereco_padrao.to_string(),
+            modelos: Arc::new(Mutex::new(HashMap::new())),
+            // Começa longe do zero para não repetir o trabalho da mineração interna
+            proximo_extra: Arc::new(AtomicU64::new((rand::random::<u32>() as u64) << 32)),
+            desligar: Arc::new(desligar),
+        }
+    }
+
+    pub fn porta(&self) -> u16 {
+        self.porta
+    }
+
+    /// Começa a aceitar mineradores em 127.0.0.1
+    pub async fn start(&self) -> Result<(), String> {
+        let listener = TcpListener::bind(("127.0.0.1", self.porta))
+            .await
+            .map_err(|e| format!("Erro ao iniciar servidor de mineração na porta {}: {}", self.porta, e))?;
+        println!("⛏️ Servidor de mineração escutando em 127.0.0.1:{}", self.porta);
+
+        tokio::spawn(self.clone().aceitar_conexoes(listener));
+        Ok(())
+    }
+
+    /// Para de aceitar mineradores e fecha as conexões abertas
+    pub fn encerrar(&self) {
+        self.desligar.send_replace(true);
+    }
+
+    /// Monta um modelo novo, com extra-nonce próprio, e o guarda para a submissão
+    pub fn modelo(&self, endereco: Option<&str>) -> ModeloBloco {
+        let mut bloco = self.minerador.modelo(endereco.unwrap_or(&self.endereco_padrao));
+        let extra = self.proximo_extra.fetch_add(1, Ordering::Relaxed);
+        aplicar_extra_nonce(&mut bloco, extra);
+
+        let modelo = ModeloBloco {
+            id: format!("{:016x}", extra),
+            altura: bloco.index,
+            version: bloco.header.version,
+            previous_hash: bloco.header.previous_hash.clone(),
+            merkle_root: bloco.header.merkle_root.clone(),
+            reclaimed_hash: bloco.header.reclaimed_hash.clone(),
+            keepalive_hash: bloco.header.keepalive_hash.clone(),
+            extra_reward: bloco.header.extra_reward,
+            timestamp: bloco.header.timestamp,
+            bits: bloco.header.bits,
+            alvo: Alvo::de_bits(bloco.header.bits).map(|a| a.para_hex()).unwrap_or_default(),
+            cabecalho: hex::encode(codificar(&bloco.header)),
+            coinbase: bloco.transactions[0].outputs.clone(),
+            transacoes: bloco.transactions[1..].to_vec(),
+            reabsorvidos: bloco.reclaimed.clone(),
+            provas_de_vida: bloco.keepalives.clone(),
+        };
+
+        // Modelos de outro topo ou antigos demais não são mais aceitos
+        let mut modelos = self.modelos.lock().unwrap();
+        modelos.retain(|&id, b| {
+            b.header.previous_hash == bloco.header.previous_hash && extra.wrapping_sub(id) < MAX_MODELOS
+        });
+        modelos.insert(extra, bloco);
+        modelo
+    }
+
+    /// Confere o cabeçalho resolvido do modelo `id` com `nonce` e, se o hash
+    /// atingir o alvo, submete o bloco à cadeia. Devolve o hash e a altura.
+    pub fn submeter(&self, id: &str, nonce: u64) -> Result<(String, u64), String> {
+        let extra = u64::from_str_radix(id, 16).map_err(|_| format!("id de modelo inválido: {}", id))?;
+        let mut bloco = self
+            .modelos
+            .lock()
+            .unwrap()
+            .get(&extra)
+            .cloned()
+            .ok_or_else(|| format!("modelo {} desconhecido ou vencido", id))?;
+
+        bloco.header.nonce = nonce;
+        bloco.hash = bloco.header.hash();
+        let alvo = Alvo::de_bits(bloco.header.bits).ok_or("bits do modelo não formam um alvo válido")?;
+        if !alvo.aceita_hex(&bloco.hash) {
+            return Err(format!("hash {} não atinge o alvo", bloco.hash));
+        }
+
+        let (hash, altura) = (bloco.hash.clone(), bloco.index);
+        if !self.minerador.submeter(bloco)? {
+            return Err(format!("bloco {} não entrou na cadeia ativa", hash));
+        }
+        self.modelos.lock().unwrap().remove(&extra);
+        println!("⛏️ Bloco #{} minerado externamente ({})", altura, hash);
+        Ok((hash, altura))
+    }
+
+    /// Atende uma requisição já decodificada
+    pub fn atender(&self, requisicao: Requisicao) -> Resposta {
+        match requisicao {
+            Requisicao::Modelo { endereco } => Resposta::Modelo(Box::new(self.modelo(endereco.as_deref()))),
+            Requisicao::Submeter { id, nonce } => match self.submeter(&id, nonce) {
+                Ok((hash, altura)) => Resposta::Aceito { hash, altura },
+                Err(erro) => Resposta::Recusado { erro },
+            },
+        }
+    }
+
+    /// Aceita conexões até o servidor ser encerrado
+    async fn aceitar_conexoes(self, listener: TcpListener) {
+        let mut desligar = self.desligar.subscribe();
+        loop {
+            let stream = tokio::select! {
+                conexao = listener.accept() => match conexao {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        println!("⚠️ Erro ao aceitar minerador: {}", e);
+                        continue;
+                    }
+                },
+                _ = desligar.wait_for(|d| *d) => return,
+            };
+            tokio::spawn(self.clone().atender_conexao(stream));
+        }
+    }
+
+    /// Lê requisições linha a linha e responde cada uma na mesma ordem
+    async fn atender_conexao(self, stream: TcpStream) {
+        let mut desligar = self.desligar.subscribe();
+        let (leitura, mut escrita) = stream.into_split();
+        let mut leitor = BufReader::new(leitura);
+
+        loop {
+            let mut linha = String::new();
+            let mut limitado = (&mut leitor).take(MAX_REQUISICAO);
+            let lido = tokio::select! {
+                lido = limitado.read_line(&mut linha) => lido,
+                _ = desligar.wait_for(|d| *d) => return,
+            };
+            match lido {
+                Ok(0) | Err(_) => return,
+                Ok(_) if !linha.ends_with('\n') => return, // linha longa demais
+                Ok(_) => {}
+            }
+            if linha.trim().is_empty() {
+                continue;
+            }
+
+            // Montar o modelo e conectar o bloco seguram o lock da blockchain: fora do runtime
+            let resposta = match serde_json::from_str::<Requisicao>(&linha) {
+                Ok(requisicao) => {
+                    let servidor = self.clone();
+                    tokio::task::spawn_blocking(move || servidor.atender(requisicao))
+                        .await
+                        .expect("tarefa do servidor de mineração interrompida")
+                }
+                Err(e) => Resposta::Recusado { erro: format!("requisição inválida: {}", e) },
+            };
+            let mut saida = serde_json::to_vec(&resposta).unwrap_or_default();
+            saida.push(b'\n');
+            if escrita.write_all(&saida).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Envia uma requisição ao servidor de mineração em `endereco` e espera a resposta
+pub async fn requisitar(endereco: &str, requisicao: &Requisicao) -> Result<Resposta, String> {
+    let stream = TcpStream::connect(endereco)
+        .await
+        .map_err(|e| format!("Erro ao conectar ao servidor de mineração {}: {}", endereco, e))?;
+    let (leitura, mut escrita) = stream.into_split();
+
+    let mut linha = serde_json::to_vec(requisicao).map_err(|e| e.to_string())?;
+    linha.push(b'\n');
+    escrita.write_all(&linha).await.map_err(|e| e.to_string())?;
+
+    let mut resposta = String::new();
+    BufReader::new(leitura).read_line(&mut resposta).await.map_err(|e| e.to_string())?;
+    serde_json::from_str(&resposta).map_err(|e| format!("resposta inválida: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{BlockHeader, TIMESTAMP_GENESIS};
+    use crate::blockchain::Blockchain;
+    use crate::codificacao::decodificar;
+    use crate::mempool::Mempool;
+    use crate::relogio::RelogioManual;
+
+    fn servidor(porta: u16) -> (ServidorMineracao, Arc<Mutex<Blockchain>>) {
+        let mut blockchain = Blockchain::new();
+        blockchain.usar_relogio(Arc::new(RelogioManual::new(TIMESTAMP_GENESIS + 24 * 60 * 60)));
+        let blockchain = Arc::new(Mutex::new(blockchain));
+        let minerador = Minerador::new(Arc::clone(&blockchain), Arc::new(Mutex::new(Mempool::new())));
+        (ServidorMineracao::new(minerador, porta, "minerador"), blockchain)
+    }
+
+    /// Primeiro nonce cujo hash do cabeçalho atinge (ou não) o alvo do modelo
+    fn nonce(modelo: &ModeloBloco, atinge: bool) -> u64 {
+        let mut cabecalho: BlockHeader = decodificar(&hex::decode(&modelo.cabecalho).unwrap()).unwrap();
+        let alvo = Alvo::de_bits(modelo.bits).unwrap();
+        (0..)
+            .find(|&nonce| {
+                cabecalho.nonce = nonce;
+                alvo.aceita_hex(&cabecalho.hash()) == atinge
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn recusa_modelo_desconhecido_ou_vencido() {
+        let (servidor, _) = servidor(PORTA_MINERACAO);
+        assert!(servidor.submeter("xyz", 0).unwrap_err().contains("inválido"));
+
+        let primeiro = servidor.modelo(None);
+        let desconhecido = format!("{:016x}", u64::from_str_radix(&primeiro.id, 16).unwrap() - 1);
+        assert!(servidor.submeter(&desconhecido, 0).unwrap_err().contains("desconhecido"));
+
+        // Depois de MAX_MODELOS modelos novos, o primeiro vence mesmo resolvido
+        let resolvido = nonce(&primeiro, true);
+        for _ in 0..MAX_MODELOS {
+            servidor.modelo(None);
+        }
+        assert!(servidor.submeter(&primeiro.id, resolvido).unwrap_err().contains("vencido"));
+    }
+
+    #[test]
+    fn recusa_nonce_que_nao_atinge_o_alvo() {
+        let (servidor, blockchain) = servidor(PORTA_MINERACAO);
+        let modelo = servidor.modelo(None);
+
+        let erro = servidor.submeter(&modelo.id, nonce(&modelo, false)).unwrap_err();
+        assert!(erro.contains("não atinge o alvo"), "{}", erro);
+        assert_eq!(blockchain.lock().unwrap().height(), 1);
+
+        // O modelo continua valendo para uma solução de verdade
+        let (_, altura) = servidor.submeter(&modelo.id, nonce(&modelo, true)).unwrap();
+        assert_eq!(altura, 1);
+        assert_eq!(blockchain.lock().unwrap().height(), 2);
+    }
+
+    #[test]
+    fn modelo_de_topo_antigo_sai_quando_o_topo_muda() {
+        let (servidor, blockchain) = servidor(PORTA_MINERACAO);
+        let antigo = servidor.modelo(None);
+        let vencedor = servidor.modelo(None);
+        servidor.submeter(&vencedor.id, nonce(&vencedor, true)).unwrap();
+
+        // O próximo modelo já é do topo novo e descarta os do topo anterior
+        let novo = servidor.modelo(None);
+        assert_eq!(novo.previous_hash, blockchain.lock().unwrap().latest_hash());
+        assert_ne!(novo.previous_hash, antigo.previous_hash);
+        let erro = servidor.submeter(&antigo.id, nonce(&antigo, true)).unwrap_err();
+        assert!(erro.contains("desconhecido ou vencido"), "{}", erro);
+        assert_eq!(blockchain.lock().unwrap().height(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn minerador_externo_pede_modelo_e_submete_pela_rede() {
+        let (servidor, blockchain) = servidor(39_550);
+        servidor.start().await.unwrap();
+        let endereco = format!("127.0.0.1:{}", servidor.porta());
+
+        let modelo = match requisitar(&endereco, &Requisicao::Modelo { endereco: Some("externo".to_string()) }).await {
+            Ok(Resposta::Modelo(modelo)) => modelo,
+            outra => panic!("esperava um modelo, veio {:?}", outra),
+        };
+        assert_eq!(modelo.altura, 1);
+        assert_eq!(modelo.coinbase[0].address, "externo");
+
+        let submissao = Requisicao::Submeter { id: modelo.id.clone(), nonce: nonce(&modelo, true) };
+        match requisitar(&endereco, &submissao).await {
+            Ok(Resposta::Aceito { hash, altura }) => {
+                assert_eq!(altura, 1);
+                assert_eq!(hash, blockchain.lock().unwrap().latest_hash());
+            }
+            outra => panic!("esperava o bloco aceito, veio {:?}", outra),
+        }
+
+        // Repetir a submissão é recusado: o modelo já foi usado
+        assert!(matches!(requisitar(&endereco, &submissao).await, Ok(Resposta::Recusado { .. })));
+        servidor.encerrar();
+    }
+}
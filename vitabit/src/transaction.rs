@@ -1,116 +1,390 @@
-use serde::{Serialize, Deserialize};
-use sha2::{Sha256, Digest};
-use ripemd::Ripemd160;
-use base58::ToBase58;
-use secp256k1::{Secp256k1, PublicKey, Message};
-use secp256k1::ecdsa::Signature;
-use chrono::Utc;
-
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TxInput {
-    pub txid: String,
-    pub index: usize,
-    pub signature: String,
-    pub pubkey: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TxOutput {
-    pub value: u64,
-    pub address: String,
-    pub timestamp: i64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    pub id: String,
-    pub inputs: Vec<TxInput>,
-    pub outputs: Vec<TxOutput>,
-}
-
-impl TxInput {
-    /// Verifica se a entrada é válida comparando assinatura e endereço
-    pub fn is_valid(&self, original_output: &TxOutput) -> bool {
-        let secp = Secp256k1::new();
-
-        let pubkey_bytes = match hex::decode(&self.pubkey) {
-            Ok(b) => b,
-            Err(_) => return false,
-        };
-
-        let pubkey = match PublicKey::from_slice(&pubkey_bytes) {
-            Ok(p) => p,
-            Err(_) => return false,
-        };
-
-        // Deriva o endereço da pubkey
-        let sha256 = Sha256::digest(&pubkey.serialize());
-        let ripemd = Ripemd160::digest(&sha256);
-        let mut payload = vec![0x00];
-        payload.extend(&ripemd);
-        let checksum = &Sha256::digest(&Sha256::digest(&payload))[0..4];
-        payload.extend(checksum);
-        let derived_address = payload.to_base58();
-
-        if derived_address != original_output.address {
-            return false;
-        }
-
-        // Recria a mensagem e verifica a assinatura
-        let msg_hash = Sha256::digest(format!("{}{}", self.txid, self.index).as_bytes());
-        let msg = match Message::from_digest_slice(&msg_hash) {
-            Ok(m) => m,
-            Err(_) => return false,
-        };
-
-        let sig_bytes = match hex::decode(&self.signature) {
-            Ok(b) => b,
-            Err(_) => return false,
-        };
-
-        let signature = match Signature::from_der(&sig_bytes) {
-            Ok(s) => s,
-            Err(_) => return false,
-        };
-
-        secp.verify_ecdsa(&msg, &signature, &pubkey).is_ok()
-    }
-}
-
-impl Transaction {
-    /// Cria uma transação normal com inputs/outputs
-    pub fn new(inputs: Vec<TxInput>, outputs: Vec<TxOutput>) -> Self {
-        let mut tx = Transaction {
-            id: String::new(),
-            inputs,
-            outputs,
-        };
-        tx.id = tx.calculate_hash();
-        tx
-    }
-
-    /// Cria uma transação coinbase (bloco de mineração)
-    pub fn new_coinbase(to_address: &str, reward: u64) -> Self {
-        let input = TxInput {
-            txid: "0".to_string(),
-            index: 0,
-            signature: "coinbase".to_string(),
-            pubkey: "coinbase".to_string(),
-        };
-        let output = TxOutput {
-            value: reward,
-            address: to_address.to_string(),
-            timestamp: Utc::now().timestamp(),
-            
-        };
-        Transaction::new(vec![input], vec![output])
-    }
-
-    /// Gera o hash da transação
-    fn calculate_hash(&self) -> String {
-        let raw = format!("{:?}{:?}", self.inputs, self.outputs);
-        let hash = Sha256::digest(raw.as_bytes());
-        format!("{:x}", hash)
-    }
-}
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use ripemd::Ripemd160;
+use base58::ToBase58;
+use secp256k1::PublicKey;
+use chrono::Utc;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::codificacao::codificar;
+use crate::assinatura::{Signer, Verifier};
+use crate::utxo::{UTXOSet, BLOCOS_100_ANOS, MATURIDADE_COINBASE};
+
+/// A assinatura cobre todas as entradas e todos os outputs
+pub const SIGHASH_ALL: u8 = 0x01;
+/// A assinatura cobre só o output de mesma posição da entrada
+pub const SIGHASH_SINGLE: u8 = 0x03;
+/// Combinado com ALL ou SINGLE: cobre só a própria entrada, outras podem ser adicionadas
+pub const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+/// Por quantos blocos, a partir da sua altura, uma prova de vida pode entrar na cadeia (~1 dia)
+pub const VALIDADE_PROVA_DE_VIDA: u64 = 144;
+
+/// Quantos blocos antes da reabsorção um output passa a aceitar prova de vida
+/// (~10 anos): fora dessa janela a renovação é recusada, então cada output
+/// rende no máximo uma prova a cada ~90 anos
+pub const JANELA_RENOVACAO: u64 = BLOCOS_100_ANOS / 10;
+
+/// Máximo de outputs renovados por uma prova de vida
+pub const MAX_OUTPOINTS_PROVA: usize = 1000;
+
+/// Referência a um output: a transação que o criou e a posição dele nela
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: String,
+    pub vout: usize,
+}
+
+impl OutPoint {
+    pub fn new(txid: &str, vout: usize) -> Self {
+        OutPoint { txid: txid.to_string(), vout }
+    }
+}
+
+impl fmt::Display for OutPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.txid, self.vout)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxInput {
+    pub txid: String,
+    pub index: usize,
+    pub signature: String,
+    pub pubkey: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxOutput {
+    pub value: u64,
+    pub address: String,
+    pub timestamp: i64, // informado por quem cria a transação; o consenso não depende dele
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub id: String,
+    pub inputs: Vec<TxInput>,
+    pub outputs: Vec<TxOutput>,
+}
+
+/// Motivo da rejeição de uma transação pela validação contextual
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxValidationError {
+    IdDivergente,
+    SemEntradas,
+    SemSaidas,
+    CoinbaseAvulsa,
+    EntradaDuplicada(OutPoint),
+    EntradaInexistente(OutPoint),
+    AssinaturaInvalida(OutPoint),
+    CoinbaseImatura(OutPoint),
+    SaidaZerada(usize),
+    Overflow,
+    SaidasExcedemEntradas { entradas: u64, saidas: u64 },
+}
+
+impl fmt::Display for TxValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxValidationError::IdDivergente => write!(f, "txid não confere com o conteúdo da transação"),
+            TxValidationError::SemEntradas => write!(f, "transação sem inputs"),
+            TxValidationError::SemSaidas => write!(f, "transação sem outputs"),
+            TxValidationError::CoinbaseAvulsa => write!(f, "coinbase fora da primeira posição do bloco"),
+            TxValidationError::EntradaDuplicada(o) => write!(f, "output {} gasto duas vezes na mesma transação", o),
+            TxValidationError::EntradaInexistente(o) => write!(f, "output {} inexistente ou já gasto", o),
+            TxValidationError::AssinaturaInvalida(o) => write!(f, "assinatura inválida para {}", o),
+            TxValidationError::CoinbaseImatura(o) => {
+                write!(f, "output de coinbase {} ainda não tem {} confirmações", o, MATURIDADE_COINBASE)
+            }
+            TxValidationError::SaidaZerada(i) => write!(f, "output {} com valor zero", i),
+            TxValidationError::Overflow => write!(f, "overflow na soma dos valores"),
+            TxValidationError::SaidasExcedemEntradas { entradas, saidas } => {
+                write!(f, "outputs ({}) excedem os inputs ({})", saidas, entradas)
+            }
+        }
+    }
+}
+
+impl TxInput {
+    /// Output gasto por esta entrada
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(&self.txid, self.index)
+    }
+}
+
+impl Transaction {
+    /// Cria uma transação normal com inputs/outputs
+    pub fn new(inputs: Vec<TxInput>, outputs: Vec<TxOutput>) -> Self {
+        let mut tx = Transaction {
+            id: String::new(),
+            inputs,
+            outputs,
+        };
+        tx.id = tx.calculate_hash();
+        tx
+    }
+
+    /// Cria uma transação coinbase (bloco de mineração). A altura do bloco vai
+    /// na entrada para que duas coinbases nunca tenham o mesmo txid; a pubkey
+    /// dela guarda o extra-nonce, começando em zero.
+    pub fn new_coinbase(to_address: &str, reward: u64, altura: u64) -> Self {
+        let input = TxInput {
+            txid: "0".to_string(),
+            index: altura as usize,
+            signature: "coinbase".to_string(),
+            pubkey: extra_nonce_coinbase(0),
+        };
+        let output = TxOutput {
+            value: reward,
+            address: to_address.to_string(),
+            timestamp: Utc::now().timestamp(),
+            
+        };
+        Transaction::new(vec![input], vec![output])
+    }
+
+    /// Resumo assinado pela entrada `entrada`, conforme o tipo de sighash; `gastos`
+    /// traz o output gasto por cada entrada, na ordem das entradas. Inclui o tipo, as
+    /// entradas cobertas (outpoint e valor gasto; a da própria entrada leva também a
+    /// pubkey), o endereço do output gasto por ela e os outputs cobertos. As
+    /// assinaturas ficam de fora. Retorna `None` para tipo desconhecido, SINGLE sem
+    /// output correspondente ou `gastos` que não acompanha as entradas.
+    pub fn sighash(&self, entrada: usize, gastos: &[TxOutput], tipo: u8) -> Option<[u8; 32]> {
+        let input = self.inputs.get(entrada)?;
+        if gastos.len() != self.inputs.len() {
+            return None;
+        }
+        let gasto = &gastos[entrada];
+
+        let entradas: Vec<(OutPoint, &str, u64)> = if tipo & SIGHASH_ANYONECANPAY != 0 {
+            vec![(input.outpoint(), input.pubkey.as_str(), gasto.value)]
+        } else {
+            self.inputs
+                .iter()
+                .zip(gastos)
+                .enumerate()
+                .map(|(i, (inp, g))| (inp.outpoint(), if i == entrada { inp.pubkey.as_str() } else { "" }, g.value))
+                .collect()
+        };
+
+        let outputs: &[TxOutput] = match tipo & !SIGHASH_ANYONECANPAY {
+            SIGHASH_ALL => &self.outputs,
+            SIGHASH_SINGLE => std::slice::from_ref(self.outputs.get(entrada)?),
+            _ => return None,
+        };
+
+        let dados = codificar(&(tipo, entradas, &gasto.address, outputs));
+        Some(Sha256::digest(Sha256::digest(dados)).into())
+    }
+
+    /// Assina a entrada `entrada` com `signer`, gravando nela a pubkey e a assinatura
+    /// (o DER seguido do tipo de sighash)
+    pub fn assinar_entrada(&mut self, entrada: usize, gastos: &[TxOutput], tipo: u8, signer: &impl Signer) -> Option<()> {
+        self.inputs.get_mut(entrada)?.pubkey = hex::encode(signer.chave_publica().serialize());
+        let mut assinatura = signer.assinar(&self.sighash(entrada, gastos, tipo)?);
+        assinatura.push(tipo);
+        self.inputs[entrada].signature = hex::encode(assinatura);
+        self.id = self.calculate_hash();
+        Some(())
+    }
+
+    /// Verifica a assinatura da entrada `entrada` e se a pubkey é dona do output
+    /// que ela gasta; `gastos` traz o output gasto por cada entrada
+    pub fn verificar_entrada(&self, entrada: usize, gastos: &[TxOutput]) -> bool {
+        let (Some(input), Some(gasto)) = (self.inputs.get(entrada), gastos.get(entrada)) else {
+            return false;
+        };
+
+        let pubkey = match hex::decode(&input.pubkey).ok().and_then(|b| PublicKey::from_slice(&b).ok()) {
+            Some(p) => p,
+            None => return false,
+        };
+        if endereco_da_pubkey(&pubkey) != gasto.address {
+            return false;
+        }
+
+        // O último byte da assinatura diz o que ela cobre
+        let mut sig_bytes = match hex::decode(&input.signature) {
+            Ok(b) => b,
+            Err(_) => return false,
+        };
+        let Some(tipo) = sig_bytes.pop() else {
+            return false;
+        };
+        match self.sighash(entrada, gastos, tipo) {
+            Some(resumo) => pubkey.verificar(&resumo, &sig_bytes),
+            None => false,
+        }
+    }
+
+    /// Validação contextual de uma transação comum contra o conjunto de UTXOs:
+    /// txid que confere, entradas existentes, distintas, maduras e assinadas,
+    /// outputs não nulos e sem criar valor. Retorna a taxa implícita (inputs - outputs).
+    pub fn validar(&self, utxo_set: &UTXOSet) -> Result<u64, TxValidationError> {
+        if !self.id_confere() {
+            return Err(TxValidationError::IdDivergente);
+        }
+        if self.inputs.is_empty() {
+            return Err(TxValidationError::SemEntradas);
+        }
+        if self.outputs.is_empty() {
+            return Err(TxValidationError::SemSaidas);
+        }
+        if self.inputs.iter().any(|i| i.signature == "coinbase") {
+            return Err(TxValidationError::CoinbaseAvulsa);
+        }
+
+        let mut vistos = HashSet::new();
+        if let Some(repetida) = self.inputs.iter().map(|i| i.outpoint()).find(|o| !vistos.insert(o.clone())) {
+            return Err(TxValidationError::EntradaDuplicada(repetida));
+        }
+
+        let mut saidas: u64 = 0;
+        for (i, output) in self.outputs.iter().enumerate() {
+            if output.value == 0 {
+                return Err(TxValidationError::SaidaZerada(i));
+            }
+            saidas = saidas.checked_add(output.value).ok_or(TxValidationError::Overflow)?;
+        }
+
+        let mut gastos = Vec::with_capacity(self.inputs.len());
+        let mut entradas: u64 = 0;
+        for input in &self.inputs {
+            let outpoint = input.outpoint();
+            let gasto = match utxo_set.get(&outpoint) {
+                Some(g) => g,
+                None => return Err(TxValidationError::EntradaInexistente(outpoint)),
+            };
+            if !utxo_set.maduro(&outpoint) {
+                return Err(TxValidationError::CoinbaseImatura(outpoint));
+            }
+            entradas = entradas.checked_add(gasto.value).ok_or(TxValidationError::Overflow)?;
+            gastos.push(gasto.clone());
+        }
+
+        // As assinaturas cobrem os valores de todas as entradas: só dá para conferir com todas à mão
+        for (i, input) in self.inputs.iter().enumerate() {
+            if !self.verificar_entrada(i, &gastos) {
+                return Err(TxValidationError::AssinaturaInvalida(input.outpoint()));
+            }
+        }
+
+        if saidas > entradas {
+            return Err(TxValidationError::SaidasExcedemEntradas { entradas, saidas });
+        }
+        Ok(entradas - saidas)
+    }
+
+    /// Indica se o txid é o hash do conteúdo da transação
+    pub fn id_confere(&self) -> bool {
+        self.id == self.calculate_hash()
+    }
+
+    /// Gera o hash da transação
+    fn calculate_hash(&self) -> String {
+        let hash = Sha256::digest(codificar(&(&self.inputs, &self.outputs)));
+        format!("{:x}", hash)
+    }
+}
+
+/// Prova de vida: o dono de uma chave declara que ainda controla os outputs
+/// listados, sem gastá-los, e renova a contagem deles na regra dos 100 anos.
+/// Não paga taxa nem mexe em valores.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvaDeVida {
+    pub outpoints: Vec<OutPoint>, // Em ordem crescente, sem repetição
+    pub altura: u64,              // Primeiro bloco em que vale; impede reaproveitar a assinatura depois
+    pub pubkey: String,
+    pub signature: String,        // DER sobre o resumo da prova
+}
+
+impl ProvaDeVida {
+    /// Prova assinada por `signer` para os outputs dele, válida a partir do bloco `altura`
+    pub fn new(mut outpoints: Vec<OutPoint>, altura: u64, signer: &impl Signer) -> Self {
+        outpoints.sort();
+        outpoints.dedup();
+        let mut prova = ProvaDeVida {
+            outpoints,
+            altura,
+            pubkey: hex::encode(signer.chave_publica().serialize()),
+            signature: String::new(),
+        };
+        prova.signature = hex::encode(signer.assinar(&prova.resumo()));
+        prova
+    }
+
+    /// Identificador da prova (SHA256 da codificação completa)
+    pub fn id(&self) -> String {
+        format!("{:x}", Sha256::digest(codificar(self)))
+    }
+
+    /// Resumo assinado: os outputs, a altura e a chave, com um prefixo que o
+    /// distingue de qualquer sighash de transação
+    fn resumo(&self) -> [u8; 32] {
+        let dados = codificar(&("PROVA_DE_VIDA", &self.outpoints, self.altura, &self.pubkey));
+        Sha256::digest(Sha256::digest(dados)).into()
+    }
+
+    /// Valida a prova para entrar no bloco `altura_bloco`: dentro da validade,
+    /// assinada pela chave dona de todos os outputs, sem output que já tenha
+    /// sido renovado (ou criado) na altura da prova ou depois e com todos eles
+    /// na JANELA_RENOVACAO antes da reabsorção
+    pub fn validar(&self, utxo_set: &UTXOSet, altura_bloco: u64) -> Result<(), String> {
+        if self.outpoints.is_empty() || self.outpoints.len() > MAX_OUTPOINTS_PROVA {
+            return Err(format!("deve listar de 1 a {} outputs", MAX_OUTPOINTS_PROVA));
+        }
+        if !self.outpoints.windows(2).all(|par| par[0] < par[1]) {
+            return Err("outputs fora de ordem ou repetidos".to_string());
+        }
+        if altura_bloco < self.altura || altura_bloco - self.altura >= VALIDADE_PROVA_DE_VIDA {
+            return Err(format!("vale do bloco #{} ao #{}", self.altura, self.altura + VALIDADE_PROVA_DE_VIDA - 1));
+        }
+
+        let pubkey = hex::decode(&self.pubkey)
+            .ok()
+            .and_then(|b| PublicKey::from_slice(&b).ok())
+            .ok_or("chave pública inválida")?;
+        let assinatura = hex::decode(&self.signature).map_err(|_| "assinatura inválida")?;
+        if !pubkey.verificar(&self.resumo(), &assinatura) {
+            return Err("assinatura inválida".to_string());
+        }
+
+        let endereco = endereco_da_pubkey(&pubkey);
+        for outpoint in &self.outpoints {
+            let output = utxo_set
+                .get(outpoint)
+                .ok_or_else(|| format!("output {} inexistente ou gasto", outpoint))?;
+            if output.address != endereco {
+                return Err(format!("output {} não pertence à chave", outpoint));
+            }
+            let Some(origem) = utxo_set.origem(outpoint).filter(|o| o.vivo_desde < self.altura) else {
+                return Err(format!("output {} já renovado depois do bloco #{}", outpoint, self.altura));
+            };
+            let renovavel_em = (origem.vivo_desde + BLOCOS_100_ANOS).saturating_sub(JANELA_RENOVACAO);
+            if altura_bloco < renovavel_em {
+                return Err(format!("output {} só pode ser renovado a partir do bloco #{}", outpoint, renovavel_em));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Extra-nonce gravado na entrada da coinbase: sempre 16 dígitos hex, para que
+/// trocá-lo não mude o tamanho do bloco
+pub fn extra_nonce_coinbase(extra: u64) -> String {
+    format!("{:016x}", extra)
+}
+
+/// Deriva o endereço VBIT (Base58Check de RIPEMD160(SHA256(pubkey))) de uma chave pública
+fn endereco_da_pubkey(pubkey: &PublicKey) -> String {
+    let sha256 = Sha256::digest(pubkey.serialize());
+    let ripemd = Ripemd160::digest(sha256);
+    let mut payload = vec![0x00];
+    payload.extend(&ripemd);
+    let checksum = &Sha256::digest(Sha256::digest(&payload))[0..4];
+    payload.extend(checksum);
+    payload.to_base58()
+}
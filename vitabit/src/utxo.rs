@@ -1,146 +1,458 @@
-// src/utxo.rs
-
-use crate::blockchain::Blockchain;
-use crate::transaction::{Transaction, TxInput, TxOutput};
-use crate::block::Block; 
-
-use std::collections::HashMap;
-
-const SEGUNDOS_100_ANOS: i64 = 100 * 365 * 24 * 60 * 60; // 100 anos, sem bissexto
-
-/// Conjunto de UTXOs não gastos
-#[derive(Debug, Clone)]
-pub struct UTXOSet {
-    pub utxos: HashMap<String, Vec<(usize, TxOutput)>>, // txid → (índice, output)
-}
-
-impl UTXOSet {
-
-
-    pub fn remove(&mut self, txid: &str, index: usize) {
-        if let Some(outputs) = self.utxos.get_mut(txid) {
-            outputs.retain(|(i, _)| *i != index);
-            if outputs.is_empty() {
-                self.utxos.remove(txid);
-            }
-        }
-    }
-
-
-    /// Retorna UTXOs inativos por mais de 100 anos
-    pub fn utxos_inativos(&self, _blockchain: &Blockchain, tempo_atual: i64) -> Vec<(String, usize, TxOutput)> {
-        let mut inativos = Vec::new();
-
-        for (txid, outputs) in &self.utxos {
-            for (index, output) in outputs {
-                if tempo_atual - output.timestamp >= SEGUNDOS_100_ANOS {
-                    inativos.push((txid.clone(), *index, output.clone()));
-                }
-            }
-        }
-
-        inativos
-    }
-
-    /// Cria UTXOSet completo da blockchain
-    pub fn from_blockchain(blockchain: &Blockchain) -> Self {
-        let mut spent: HashMap<String, Vec<usize>> = HashMap::new();
-        let mut utxos: HashMap<String, Vec<(usize, TxOutput)>> = HashMap::new();
-
-        for block in &blockchain.chain {
-            let txs: Vec<Transaction> = serde_json::from_str(&block.data).unwrap_or_default();
-
-            for tx in &txs {
-                // Marca entradas como gastas
-                for input in &tx.inputs {
-                    spent.entry(input.txid.clone())
-                         .or_default()
-                         .push(input.index);
-                }
-
-                // Registra outputs não gastos
-                for (index, output) in tx.outputs.iter().enumerate() {
-                    let txid = tx.id.clone();
-                    let is_spent = spent.get(&txid)
-                                        .map_or(false, |idxs| idxs.contains(&index));
-                    if !is_spent {
-                        utxos.entry(txid.clone())
-                             .or_default()
-                             .push((index, output.clone()));
-                    }
-                }
-            }
-        }
-
-        UTXOSet { utxos }
-    }
-
-    /// Filtra UTXOs por endereço
-    pub fn find_by_address(&self, address: &str) -> Vec<(String, usize, TxOutput)> {
-        let mut results = vec![];
-
-        for (txid, outputs) in &self.utxos {
-            for (index, output) in outputs {
-                if output.address == address {
-                    results.push((txid.clone(), *index, output.clone()));
-                }
-            }
-        }
-
-        results
-    }
-
-    /// Saldo total de um endereço
-    pub fn balance(&self, address: &str) -> u64 {
-        self.find_by_address(address)
-            .iter()
-            .map(|(_, _, output)| output.value)
-            .sum()
-    }
-
-    /// Construtor vazio
-    pub fn new() -> Self {
-        UTXOSet {
-            utxos: HashMap::new(),
-        }
-    }
-
-    /// Constrói UTXOSet a partir de parte da cadeia
-    pub fn from_chain_segment(chain: &[Block]) -> Self {
-        let mut utxos: HashMap<String, Vec<(usize, TxOutput)>> = HashMap::new();
-
-        for block in chain {
-            let txs: Vec<Transaction> = match serde_json::from_str(&block.data) {
-                Ok(t) => t,
-                Err(_) => continue,
-            };
-
-            for tx in &txs {
-                for (i, output) in tx.outputs.iter().enumerate() {
-                    utxos.entry(tx.id.clone())
-                         .or_default()
-                         .push((i, output.clone()));
-                }
-
-                for input in &tx.inputs {
-                    if let Some(outputs) = utxos.get_mut(&input.txid) {
-                        outputs.retain(|(i, _)| *i != input.index);
-                        if outputs.is_empty() {
-                            utxos.remove(&input.txid);
-                        }
-                    }
-                }
-            }
-        }
-
-        UTXOSet { utxos }
-    }
-
-    /// Calcula o total de VBITs não gastos (em circulação)
-    pub fn total_em_circulacao(&self) -> u64 {
-        self.utxos
-            .values()
-            .flat_map(|outputs| outputs.iter().map(|(_, out)| out.value))
-            .sum()
-    }
-}
+// src/utxo.rs
+
+use crate::blockchain::TEMPO_POR_BLOCO;
+use crate::transaction::{OutPoint, ProvaDeVida, Transaction, TxOutput};
+use crate::block::Block; 
+use crate::armazem::{ler_registros, montar_registro};
+use crate::codificacao::{codificar, decodificar};
+
+use serde::{Serialize, Deserialize};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub const SEGUNDOS_100_ANOS: i64 = 100 * 365 * 24 * 60 * 60; // 100 anos, sem bissexto
+
+/// Blocos em 100 anos no intervalo alvo: um output parado por esse tanto de
+/// blocos é reabsorvido (regra dos 100 anos)
+pub const BLOCOS_100_ANOS: u64 = (SEGUNDOS_100_ANOS / TEMPO_POR_BLOCO) as u64;
+
+/// Máximo de outputs reabsorvidos por bloco; os que passarem disso ficam para
+/// os blocos seguintes. Com ~80 bytes por outpoint, a lista cabe com folga em
+/// TAMANHO_MAX_BLOCO, e sempre sobra espaço para a coinbase.
+pub const MAX_REABSORVIDOS_POR_BLOCO: usize = 2_000;
+
+/// Quantos blocos uma saída de coinbase espera até poder ser gasta
+pub const MATURIDADE_COINBASE: u64 = 100;
+
+/// Arquivo com a fotografia do UTXOSet, para não reprocessar a cadeia ao abrir
+pub const ARQUIVO_UTXOS: &str = "utxos.dat";
+
+/// Conjunto de UTXOs não gastos, com índice por endereço
+#[derive(Debug, Clone, Default)]
+pub struct UTXOSet {
+    utxos: HashMap<OutPoint, TxOutput>,
+    por_endereco: HashMap<String, HashSet<OutPoint>>, // endereço → outpoints que ele pode gastar
+    origens: HashMap<OutPoint, Origem>,               // bloco que criou cada output
+    por_vivo_desde: BTreeMap<u64, HashSet<OutPoint>>, // `vivo_desde` → outputs, para achar os reabsorvíveis
+    proxima_altura: u64,                              // altura do próximo bloco a ser conectado
+}
+
+/// De onde veio um output: altura do bloco que o criou e se foi pela coinbase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Origem {
+    pub altura: u64,
+    pub coinbase: bool,
+    pub vivo_desde: u64, // criação ou última prova de vida: é daqui que contam os 100 anos
+}
+
+/// Outputs tirados do conjunto por um bloco (reabsorvidos e depois gastos, na
+/// ordem); com eles o bloco pode ser desconectado sem reprocessar a cadeia
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DesfazerBloco {
+    pub gastos: Vec<(OutPoint, TxOutput)>,
+    pub origens_gastas: Vec<(OutPoint, Origem)>, // origem de cada gasto que a tinha registrada
+    pub renovados: Vec<(OutPoint, u64)>,         // renovados por prova de vida, com o `vivo_desde` anterior
+}
+
+impl UTXOSet {
+
+
+    /// Busca um output não gasto pelo outpoint
+    pub fn get(&self, outpoint: &OutPoint) -> Option<&TxOutput> {
+        self.utxos.get(outpoint)
+    }
+
+    pub fn contem(&self, outpoint: &OutPoint) -> bool {
+        self.utxos.contains_key(outpoint)
+    }
+
+    /// Registra um output não gasto, atualizando o índice por endereço
+    pub fn inserir(&mut self, outpoint: OutPoint, output: TxOutput) {
+        self.por_endereco
+            .entry(output.address.clone())
+            .or_default()
+            .insert(outpoint.clone());
+        if let Some(anterior) = self.utxos.insert(outpoint.clone(), output) {
+            self.desindexar(&outpoint, &anterior.address);
+        }
+    }
+
+    /// Bloco que criou o output, se conhecido
+    pub fn origem(&self, outpoint: &OutPoint) -> Option<Origem> {
+        self.origens.get(outpoint).copied()
+    }
+
+    /// Altura do bloco que criou o output, se ele veio de uma coinbase
+    pub fn altura_coinbase(&self, outpoint: &OutPoint) -> Option<u64> {
+        self.origem(outpoint).filter(|o| o.coinbase).map(|o| o.altura)
+    }
+
+    /// Altura do próximo bloco, o primeiro em que um gasto feito agora pode entrar
+    pub fn proxima_altura(&self) -> u64 {
+        self.proxima_altura
+    }
+
+    /// Indica se o output já pode ser gasto no próximo bloco (coinbase só depois da maturidade)
+    pub fn maduro(&self, outpoint: &OutPoint) -> bool {
+        match self.altura_coinbase(outpoint) {
+            Some(altura) => self.proxima_altura >= altura + MATURIDADE_COINBASE,
+            None => true,
+        }
+    }
+
+    /// Remove o output, devolvendo-o se ainda não estava gasto
+    pub fn remove(&mut self, outpoint: &OutPoint) -> Option<TxOutput> {
+        self.tirar_origem(outpoint);
+        let output = self.utxos.remove(outpoint)?;
+        self.desindexar(outpoint, &output.address);
+        Some(output)
+    }
+
+    /// Tira o outpoint do índice de `endereco`, se ele não pertence mais a esse endereço
+    fn desindexar(&mut self, outpoint: &OutPoint, endereco: &str) {
+        if self.utxos.get(outpoint).is_some_and(|o| o.address == endereco) {
+            return;
+        }
+        if let Some(outpoints) = self.por_endereco.get_mut(endereco) {
+            outpoints.remove(outpoint);
+            if outpoints.is_empty() {
+                self.por_endereco.remove(endereco);
+            }
+        }
+    }
+
+    /// Registra (ou troca) a origem do output, mantendo o índice por `vivo_desde`
+    fn definir_origem(&mut self, outpoint: OutPoint, origem: Origem) {
+        self.tirar_origem(&outpoint);
+        self.por_vivo_desde.entry(origem.vivo_desde).or_default().insert(outpoint.clone());
+        self.origens.insert(outpoint, origem);
+    }
+
+    /// Apaga a origem do output e sua entrada no índice por `vivo_desde`
+    fn tirar_origem(&mut self, outpoint: &OutPoint) -> Option<Origem> {
+        let origem = self.origens.remove(outpoint)?;
+        if let Some(outpoints) = self.por_vivo_desde.get_mut(&origem.vivo_desde) {
+            outpoints.remove(outpoint);
+            if outpoints.is_empty() {
+                self.por_vivo_desde.remove(&origem.vivo_desde);
+            }
+        }
+        Some(origem)
+    }
+
+    pub fn len(&self) -> usize {
+        self.utxos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+
+    /// Todos os outputs não gastos, sem ordem definida
+    pub fn iter(&self) -> impl Iterator<Item = (&OutPoint, &TxOutput)> {
+        self.utxos.iter()
+    }
+
+
+    /// Aplica uma transação: remove os outputs gastos e registra os novos
+    pub fn aplicar_transacao(&mut self, tx: &Transaction) {
+        for input in &tx.inputs {
+            self.remove(&input.outpoint());
+        }
+        for (index, output) in tx.outputs.iter().enumerate() {
+            self.inserir(OutPoint::new(&tx.id, index), output.clone());
+        }
+    }
+
+    /// Outputs que o bloco de altura `altura` reabsorve pela regra dos 100 anos:
+    /// os criados (ou renovados por prova de vida) BLOCOS_100_ANOS blocos antes
+    /// ou mais e ainda não gastos, dos mais antigos para os mais novos (por
+    /// outpoint no empate), até MAX_REABSORVIDOS_POR_BLOCO. Só depende da
+    /// cadeia, então todo nó chega à mesma lista.
+    pub fn reabsorviveis(&self, altura: u64) -> Vec<(OutPoint, TxOutput)> {
+        let Some(limite) = altura.checked_sub(BLOCOS_100_ANOS) else {
+            return Vec::new();
+        };
+        let mut reabsorviveis = Vec::new();
+        for outpoints in self.por_vivo_desde.range(..=limite).map(|(_, outpoints)| outpoints) {
+            let mut ordenados: Vec<&OutPoint> = outpoints.iter().collect();
+            ordenados.sort();
+            for outpoint in ordenados {
+                if reabsorviveis.len() == MAX_REABSORVIDOS_POR_BLOCO {
+                    return reabsorviveis;
+                }
+                if let Some(output) = self.utxos.get(outpoint) {
+                    reabsorviveis.push((outpoint.clone(), output.clone()));
+                }
+            }
+        }
+        reabsorviveis
+    }
+
+    /// Blocos que faltam até o output ser reabsorvido (0: já pode ser reabsorvido no próximo bloco)
+    pub fn blocos_ate_reabsorcao(&self, outpoint: &OutPoint) -> Option<u64> {
+        let origem = self.origem(outpoint)?;
+        Some((origem.vivo_desde + BLOCOS_100_ANOS).saturating_sub(self.proxima_altura))
+    }
+
+    /// UTXOs de um endereço, ordenados por outpoint
+    pub fn find_by_address(&self, address: &str) -> Vec<(OutPoint, TxOutput)> {
+        let mut results: Vec<(OutPoint, TxOutput)> = self
+            .por_endereco
+            .get(address)
+            .into_iter()
+            .flatten()
+            .filter_map(|outpoint| Some((outpoint.clone(), self.utxos.get(outpoint)?.clone())))
+            .collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        results
+    }
+
+    /// Saldo total de um endereço
+    pub fn balance(&self, address: &str) -> u64 {
+        self.por_endereco
+            .get(address)
+            .into_iter()
+            .flatten()
+            .filter_map(|outpoint| self.utxos.get(outpoint))
+            .map(|output| output.value)
+            .sum()
+    }
+
+    /// Construtor vazio
+    pub fn new() -> Self {
+        UTXOSet::default()
+    }
+
+    /// Constrói UTXOSet a partir de parte da cadeia
+    pub fn from_chain_segment(chain: &[Block]) -> Self {
+        Self::from_blocos(chain)
+    }
+
+    /// Constrói UTXOSet percorrendo blocos em ordem (em memória ou lidos do disco)
+    pub fn from_blocos<I>(blocos: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Block>,
+    {
+        let mut utxo_set = UTXOSet::new();
+        for block in blocos {
+            utxo_set.aplicar_bloco(block.borrow());
+        }
+        utxo_set
+    }
+
+    /// Aplica todas as transações de um bloco
+    pub fn aplicar_bloco(&mut self, block: &Block) {
+        self.conectar_bloco(block);
+    }
+
+    /// Aplica o bloco e devolve o registro para desfazê-lo depois. Os outputs
+    /// reabsorvidos saem e os das provas de vida são renovados antes das
+    /// transações, que já não podem gastar os reabsorvidos.
+    pub fn conectar_bloco(&mut self, block: &Block) -> DesfazerBloco {
+        let mut desfazer = DesfazerBloco::default();
+        self.reabsorver(&block.reclaimed, &mut desfazer);
+        for prova in &block.keepalives {
+            self.renovar(prova, block.index, &mut desfazer);
+        }
+        for (posicao, tx) in block.transactions.iter().enumerate() {
+            self.conectar_transacao(tx, posicao == 0, block.index, &mut desfazer);
+        }
+        self.concluir_conexao(block.index);
+        desfazer
+    }
+
+    // As etapas de `conectar_bloco`, na ordem, para quem precisa validar entre
+    // uma e outra sem copiar o conjunto (ver `Blockchain::conectar_validado`)
+
+    /// Tira os outputs reabsorvidos pelo bloco
+    pub fn reabsorver(&mut self, outpoints: &[OutPoint], desfazer: &mut DesfazerBloco) {
+        for outpoint in outpoints {
+            self.tirar(outpoint, desfazer);
+        }
+    }
+
+    /// Renova os outputs da prova de vida incluída no bloco de altura `altura`
+    pub fn renovar(&mut self, prova: &ProvaDeVida, altura: u64, desfazer: &mut DesfazerBloco) {
+        for outpoint in &prova.outpoints {
+            if let Some(origem) = self.origem(outpoint) {
+                desfazer.renovados.push((outpoint.clone(), origem.vivo_desde));
+                self.definir_origem(outpoint.clone(), Origem { vivo_desde: altura, ..origem });
+            }
+        }
+    }
+
+    /// Registra os outputs da transação (do bloco de altura `altura`) e tira os que ela gasta
+    pub fn conectar_transacao(&mut self, tx: &Transaction, coinbase: bool, altura: u64, desfazer: &mut DesfazerBloco) {
+        for (i, output) in tx.outputs.iter().enumerate() {
+            let outpoint = OutPoint::new(&tx.id, i);
+            self.inserir(outpoint.clone(), output.clone());
+            self.definir_origem(outpoint, Origem { altura, coinbase, vivo_desde: altura });
+        }
+
+        for input in &tx.inputs {
+            self.tirar(&input.outpoint(), desfazer);
+        }
+    }
+
+    /// Fecha a conexão do bloco de altura `altura`: o próximo gasto já vai para o seguinte
+    pub fn concluir_conexao(&mut self, altura: u64) {
+        self.proxima_altura = altura + 1;
+    }
+
+    /// Remove o output anotando no registro de desfazer o que saiu
+    fn tirar(&mut self, outpoint: &OutPoint, desfazer: &mut DesfazerBloco) {
+        let origem = self.origem(outpoint);
+        if let Some(output) = self.remove(outpoint) {
+            if let Some(origem) = origem {
+                desfazer.origens_gastas.push((outpoint.clone(), origem));
+            }
+            desfazer.gastos.push((outpoint.clone(), output));
+        }
+    }
+
+    /// Desfaz `conectar_bloco`: devolve os outputs gastos e apaga os criados
+    pub fn desconectar_bloco(&mut self, block: &Block, desfazer: &DesfazerBloco) {
+        self.desfazer_conexao(&block.transactions, block.index, desfazer);
+    }
+
+    /// Desfaz as etapas já aplicadas do bloco de altura `altura`, das quais
+    /// `transacoes` são as transações conectadas (todas, se ele foi conectado inteiro)
+    pub fn desfazer_conexao(&mut self, transacoes: &[Transaction], altura: u64, desfazer: &DesfazerBloco) {
+        let mut gastos = desfazer.gastos.clone();
+
+        for tx in transacoes.iter().rev() {
+            for input in tx.inputs.iter().rev() {
+                let gasto_aqui = gastos
+                    .last()
+                    .is_some_and(|(outpoint, _)| outpoint.txid == input.txid && outpoint.vout == input.index);
+                if gasto_aqui {
+                    let (outpoint, output) = gastos.pop().unwrap();
+                    self.inserir(outpoint, output);
+                }
+            }
+
+            for index in 0..tx.outputs.len() {
+                self.remove(&OutPoint::new(&tx.id, index));
+            }
+        }
+
+        // O que sobrou foi reabsorvido pelo bloco
+        for (outpoint, output) in gastos {
+            self.inserir(outpoint, output);
+        }
+        // Outputs criados e gastos no próprio bloco já saíram de vez: a origem deles não volta
+        for (outpoint, origem) in &desfazer.origens_gastas {
+            if self.contem(outpoint) {
+                self.definir_origem(outpoint.clone(), *origem);
+            }
+        }
+        for (outpoint, vivo_desde) in desfazer.renovados.iter().rev() {
+            if let Some(origem) = self.origem(outpoint) {
+                self.definir_origem(outpoint.clone(), Origem { vivo_desde: *vivo_desde, ..origem });
+            }
+        }
+        self.proxima_altura = altura;
+    }
+
+    /// Grava a fotografia do conjunto no topo `topo`, trocando o arquivo
+    /// anterior só depois que a nova está inteira no disco
+    pub fn salvar_snapshot(&self, caminho: &Path, topo: &str) -> io::Result<()> {
+        // Os índices por endereço e por `vivo_desde` ficam de fora: são refeitos ao carregar
+        let payload = codificar(&(topo, &self.utxos, &self.origens, self.proxima_altura));
+
+        let temporario = caminho.with_extension("tmp");
+        let mut file = File::create(&temporario)?;
+        file.write_all(&montar_registro(&payload))?;
+        file.sync_all()?;
+        fs::rename(&temporario, caminho)
+    }
+
+    /// Lê a fotografia gravada; devolve o topo que ela reflete e o conjunto
+    pub fn carregar_snapshot(caminho: &Path) -> Option<(String, UTXOSet)> {
+        let mut bytes = Vec::new();
+        File::open(caminho).ok()?.read_to_end(&mut bytes).ok()?;
+        let (registros, _) = ler_registros(&bytes);
+        let (topo, utxos, origens, proxima_altura): (String, HashMap<OutPoint, TxOutput>, HashMap<OutPoint, Origem>, u64) =
+            decodificar(registros.first()?.1).ok()?;
+
+        let mut utxo_set = UTXOSet::new();
+        for (outpoint, output) in utxos {
+            utxo_set.inserir(outpoint, output);
+        }
+        for (outpoint, origem) in origens {
+            utxo_set.definir_origem(outpoint, origem);
+        }
+        utxo_set.proxima_altura = proxima_altura;
+        Some((topo, utxo_set))
+    }
+
+    /// Calcula o total de VBITs não gastos (em circulação)
+    pub fn total_em_circulacao(&self) -> u64 {
+        self.utxos.values().map(|out| out.value).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TxInput;
+
+    fn gastando(outpoint: &OutPoint, valor: u64, endereco: &str) -> Transaction {
+        let input = TxInput {
+            txid: outpoint.txid.clone(),
+            index: outpoint.vout,
+            signature: String::new(),
+            pubkey: String::new(),
+        };
+        Transaction::new(vec![input], vec![TxOutput { value: valor, address: endereco.to_string(), timestamp: 0 }])
+    }
+
+    /// Tudo o que o conjunto guarda, índices inclusos
+    type Estado = (
+        HashMap<OutPoint, TxOutput>,
+        HashMap<String, HashSet<OutPoint>>,
+        HashMap<OutPoint, Origem>,
+        BTreeMap<u64, HashSet<OutPoint>>,
+        u64,
+    );
+
+    fn estado(utxos: &UTXOSet) -> Estado {
+        (
+            utxos.utxos.clone(),
+            utxos.por_endereco.clone(),
+            utxos.origens.clone(),
+            utxos.por_vivo_desde.clone(),
+            utxos.proxima_altura,
+        )
+    }
+
+    #[test]
+    fn desfazer_bloco_com_gasto_interno_volta_ao_estado_anterior() {
+        let mut utxos = UTXOSet::new();
+        let coinbase = Transaction::new_coinbase("alice", 50, 0);
+        utxos.conectar_transacao(&coinbase, true, 0, &mut DesfazerBloco::default());
+        utxos.concluir_conexao(0);
+        let antes = estado(&utxos);
+
+        // A primeira transação gasta a coinbase; a segunda gasta o output que a primeira criou
+        let primeira = gastando(&OutPoint::new(&coinbase.id, 0), 50, "bob");
+        let segunda = gastando(&OutPoint::new(&primeira.id, 0), 50, "carol");
+        let transacoes = [primeira, segunda];
+        let mut desfazer = DesfazerBloco::default();
+        for tx in &transacoes {
+            utxos.conectar_transacao(tx, false, 1, &mut desfazer);
+        }
+        utxos.concluir_conexao(1);
+        assert_eq!(utxos.len(), 1);
+        assert_eq!(utxos.origens.len(), 1);
+        assert_eq!(desfazer.origens_gastas.len(), 2);
+
+        utxos.desfazer_conexao(&transacoes, 1, &desfazer);
+        assert_eq!(estado(&utxos), antes);
+    }
+}
@@ -0,0 +1,123 @@
+// tests/comum/mod.rs
+//
+// Rede local para os testes de integração: vários nós no mesmo processo, em
+// portas de loopback
+
+use vitabit::block::Block;
+use vitabit::blockchain::Blockchain;
+use vitabit::mempool::Mempool;
+use vitabit::miner::Minerador;
+use vitabit::p2p::P2PServer;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Intervalo entre verificações em `RedeLocal::aguardar`
+const INTERVALO_VERIFICACAO: Duration = Duration::from_millis(50);
+
+/// Nó da rede local, com estado e diretório de arquivos próprios
+pub struct NoLocal {
+    pub servidor: P2PServer,
+    pub blockchain: Arc<Mutex<Blockchain>>,
+    pub diretorio: PathBuf,
+}
+
+impl NoLocal {
+    /// Endereço pelo qual os outros nós alcançam este
+    pub fn endereco(&self) -> String {
+        format!("127.0.0.1:{}", self.servidor.porta())
+    }
+
+    pub fn altura(&self) -> u64 {
+        self.blockchain.lock().unwrap().height()
+    }
+
+    pub fn topo(&self) -> String {
+        self.blockchain.lock().unwrap().latest_hash()
+    }
+
+    /// Minera (pelo `Minerador`) um bloco com as transações da mempool e o anuncia aos peers
+    pub fn minerar(&self, endereco_minerador: &str) -> Block {
+        Minerador::com_servidor(&self.servidor)
+            .minerar(endereco_minerador)
+            .expect("bloco minerado não entrou na cadeia ativa")
+    }
+}
+
+/// Vários nós VitaBit no mesmo processo, partindo do mesmo bloco gênesis,
+/// para exercitar handshake, propagação e sincronização sem máquinas extras
+pub struct RedeLocal {
+    pub nos: Vec<NoLocal>,
+}
+
+impl RedeLocal {
+    /// Sobe `quantidade` nós nas portas `porta_base`, `porta_base + 1`, ...
+    /// Cada nó grava seus arquivos em um diretório temporário próprio.
+    pub async fn iniciar(quantidade: usize, porta_base: u16) -> Result<RedeLocal, String> {
+        let mut nos = Vec::with_capacity(quantidade);
+
+        for i in 0..quantidade {
+            let porta = porta_base + i as u16;
+            let diretorio = std::env::temp_dir().join(format!("vitabit-rede-{}-{}", std::process::id(), porta));
+            fs::create_dir_all(&diretorio).map_err(|e| format!("falha ao criar {}: {}", diretorio.display(), e))?;
+
+            let blockchain = Arc::new(Mutex::new(Blockchain::new()));
+            let mempool = Arc::new(Mutex::new(Mempool::new()));
+            let servidor = P2PServer::com_diretorio(porta, Arc::clone(&blockchain), mempool, &diretorio);
+            servidor.start().await?;
+
+            nos.push(NoLocal { servidor, blockchain, diretorio });
+        }
+
+        Ok(RedeLocal { nos })
+    }
+
+    /// Abre uma conexão do nó `de` para o nó `para`
+    pub async fn conectar(&self, de: usize, para: usize) -> Result<(), String> {
+        self.nos[de].servidor.conectar_a_peer(&self.nos[para].endereco()).await
+    }
+
+    /// Liga cada nó ao seguinte, formando a linha 0 → 1 → ... → n-1
+    pub async fn conectar_em_linha(&self) -> Result<(), String> {
+        for i in 1..self.nos.len() {
+            self.conectar(i - 1, i).await?;
+        }
+        Ok(())
+    }
+
+    /// Aguarda até `condicao` valer para a rede; `false` se o prazo acabar antes
+    pub async fn aguardar<F>(&self, prazo: Duration, condicao: F) -> bool
+    where
+        F: Fn(&RedeLocal) -> bool,
+    {
+        let limite = Instant::now() + prazo;
+        while !condicao(self) {
+            if Instant::now() >= limite {
+                return false;
+            }
+            tokio::time::sleep(INTERVALO_VERIFICACAO).await;
+        }
+        true
+    }
+
+    /// Aguarda todos os nós chegarem ao mesmo topo
+    pub async fn aguardar_convergencia(&self, prazo: Duration) -> bool {
+        self.aguardar(prazo, |rede| {
+            let topo = rede.nos[0].topo();
+            rede.nos.iter().all(|no| no.topo() == topo)
+        })
+        .await
+    }
+
+    /// Encerra todos os nós e apaga seus diretórios
+    pub async fn encerrar(self) {
+        for no in &self.nos {
+            no.servidor.encerrar().await;
+        }
+        for no in self.nos {
+            let _ = fs::remove_dir_all(&no.diretorio);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// tests/rede_local.rs
+
+mod comum;
+
+use comum::RedeLocal;
+use vitabit::block::Block;
+use vitabit::protocol::{
+    escrever_mensagem_async, ler_mensagem_async, Decodificador, Message, VersaoNo, VERSAO_NO,
+};
+
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time;
+
+const PRAZO: Duration = Duration::from_secs(30);
+
+// Cada teste usa portas próprias, para poderem rodar em paralelo
+
+#[tokio::test(flavor = "multi_thread")]
+async fn bloco_minerado_chega_a_toda_a_linha() {
+    let rede = RedeLocal::iniciar(3, 39_510).await.unwrap();
+    rede.conectar_em_linha().await.unwrap();
+
+    let bloco = rede.nos[0].minerar("minerador");
+    assert!(rede.aguardar(PRAZO, |r| r.nos.iter().all(|no| no.topo() == bloco.hash)).await);
+
+    rede.encerrar().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn no_atrasado_sincroniza_e_repassa_o_topo() {
+    let rede = RedeLocal::iniciar(3, 39_520).await.unwrap();
+    for _ in 0..5 {
+        rede.nos[0].minerar("minerador");
+    }
+    assert_eq!(rede.nos[0].altura(), 6);
+
+    // O nó 1 sincroniza com o 0 ao se conectar; o 2, ligado ao 1 antes disso,
+    // só fica sabendo dos blocos pelo topo que o 1 anuncia depois
+    rede.conectar(2, 1).await.unwrap();
+    rede.conectar(1, 0).await.unwrap();
+    assert!(rede.aguardar_convergencia(PRAZO).await);
+    assert!(rede.nos.iter().all(|no| no.altura() == 6));
+
+    rede.encerrar().await;
+}
+
+/// Conexão feita à mão, como faria um peer qualquer: VERSION/VERACK e nada mais
+async fn apresentar(endereco: &str, porta_escuta: u16) -> (TcpStream, Decodificador) {
+    let mut stream = TcpStream::connect(endereco).await.unwrap();
+    let mut decodificador = Decodificador::new();
+    let versao = VersaoNo {
+        versao: VERSAO_NO,
+        altura: 0,
+        topo: Block::genesis().hash,
+        user_agent: "/teste/".to_string(),
+        porta_escuta,
+        nonce: 7,
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    escrever_mensagem_async(&mut stream, &Message::Version(versao)).await.unwrap();
+
+    let (mut versao_recebida, mut verack_recebido) = (false, false);
+    while !versao_recebida || !verack_recebido {
+        match ler_mensagem_async(&mut stream, &mut decodificador).await.unwrap() {
+            Some(Message::Version(_)) => versao_recebida = true,
+            Some(Message::Verack) => verack_recebido = true,
+            outra => panic!("handshake inesperado: {:?}", outra.map(|m| m.comando())),
+        }
+    }
+    escrever_mensagem_async(&mut stream, &Message::Verack).await.unwrap();
+    (stream, decodificador)
+}
+
+/// Lê até o peer fechar a conexão; `false` se ela continuar aberta depois do prazo
+async fn aguardar_fechamento(stream: &mut TcpStream, decodificador: &mut Decodificador) -> bool {
+    time::timeout(PRAZO, async {
+        while let Ok(Some(_)) = ler_mensagem_async(stream, decodificador).await {}
+    })
+    .await
+    .is_ok()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn blocos_nao_solicitados_levam_ao_banimento() {
+    let rede = RedeLocal::iniciar(1, 39_530).await.unwrap();
+    let no = &rede.nos[0];
+    let porta_escuta = 39_539;
+
+    let (mut stream, mut decodificador) = apresentar(&no.endereco(), porta_escuta).await;
+    assert!(rede.aguardar(PRAZO, |r| r.nos[0].servidor.get_peers().len() == 1).await);
+
+    // BLOCKS sem GET_BLOCKS pendente não passa direto: conta no limite e pune o peer
+    let blocos = Message::Blocks(vec![Block::genesis(); 16]);
+    for _ in 0..20 {
+        if escrever_mensagem_async(&mut stream, &blocos).await.is_err() {
+            break;
+        }
+    }
+    assert!(aguardar_fechamento(&mut stream, &mut decodificador).await);
+    assert!(rede.aguardar(PRAZO, |r| r.nos[0].servidor.get_peers().is_empty()).await);
+
+    // Banido, o mesmo peer é dispensado logo depois do handshake
+    let (mut stream, mut decodificador) = apresentar(&no.endereco(), porta_escuta).await;
+    assert!(aguardar_fechamento(&mut stream, &mut decodificador).await);
+    assert!(no.servidor.get_peers().is_empty());
+
+    rede.encerrar().await;
+}